@@ -1,5 +1,7 @@
 use clap::{Arg, ArgGroup, ArgMatches, App};
 use openssl::pkey::PKey;
+use std::fs::File;
+use std::io::Read;
 use std::str::FromStr;
 use std::net::SocketAddr;
 use std::sync::Arc;
@@ -12,6 +14,7 @@ use bincode;
 use blockscape_core::bin::{Bin, AsBin};
 use blockscape_core::env::*;
 use blockscape_core::network::client::ClientConfig;
+use blockscape_core::network::ShardHeaderIndex;
 use blockscape_core::network::node::NodeEndpoint;
 use blockscape_core::primitives::*;
 use blockscape_core::forging::epos::EPoS;
@@ -19,8 +22,9 @@ use blockscape_core::signer::generate_private_key;
 use blockscape_core::hash::hash_pub_key;
 use blockscape_core::time::Time;
 use blockscape_core::record_keeper::key::NetworkEntry;
-use blockscape_core::rpc::RPC;
-use blockscape_core::record_keeper::{RecordKeeperConfig, RecordKeeperIndexingStrategy};
+use blockscape_core::record_keeper::database::BLOCK_REWARD;
+use blockscape_core::rpc::{RPC, AccessControl, Credentials};
+use blockscape_core::record_keeper::{RecordKeeper, RecordKeeperConfig, RecordKeeperIndexingStrategy, Snapshot, Error, LogicError, default_block_rules, default_txn_rules};
 
 use rpc;
 use rules;
@@ -28,6 +32,14 @@ use game;
 
 use context::Context;
 
+/// Number of blocks EPoS looks back over when selecting validators. Slash proofs are checked
+/// against the same window.
+pub const VALIDATORS_SCAN: u64 = 100;
+
+/// Consensus limits on the size of blocks. These must be the same for every node on the network.
+pub const MAX_BLOCK_SIZE: u64 = 4*1024*1024; //4 MB
+pub const MAX_BLOCK_TXNS: u64 = 4096;
+
 const ADMIN_KEY: &[u8] = 
 b"-----BEGIN PUBLIC KEY-----
 MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEAyUpw2CKdIHwdHl4eTccx
@@ -104,11 +116,46 @@ pub fn parse_cmdline<'a>() -> ArgMatches<'a> {
                 .help("The maximum amount of memory reserved for storing pending transactions (i.e. not accepted into a block)")
                 .value_name("BYTES")
                 .default_value("128M"))
+            .arg(Arg::with_name("mempool-per-creator")
+                .long("mempool-per-creator")
+                .help("The maximum number of pending transactions a single account may have at once")
+                .value_name("TXNS")
+                .default_value("64"))
+            .arg(Arg::with_name("mempool-ttl")
+                .long("mempool-ttl")
+                .help("The number of seconds a pending transaction is kept before it is dropped")
+                .value_name("SECONDS")
+                .default_value("3600"))
+            .arg(Arg::with_name("orphan-pool-size")
+                .long("orphan-pool-size")
+                .help("The maximum amount of memory reserved for blocks which arrive before their parent")
+                .value_name("BYTES")
+                .default_value("16M"))
+            .arg(Arg::with_name("orphan-ttl")
+                .long("orphan-ttl")
+                .help("The number of seconds a block is kept waiting for its parent before it is dropped")
+                .value_name("SECONDS")
+                .default_value("600"))
             .arg(Arg::with_name("indexing")
                 .long("indexing")
                 .short("I")
-                .help("The indexing strategy utilized by RecordKeeper, should be modified to your requirements (either 'full', 'standard', or 'light')")
+                .help("The indexing strategy utilized by RecordKeeper, should be modified to your requirements (checkers cannot use 'light' since it replays games from their first move)")
+                .possible_values(&["full", "standard"])
                 .default_value("standard"))
+            .arg(Arg::with_name("finality-depth")
+                .long("finality-depth")
+                .help("The number of blocks after which a block is final and can no longer be reorged (light indexing prunes state older than this)")
+                .value_name("BLOCKS")
+                .default_value("1000"))
+            .arg(Arg::with_name("snapshot")
+                .long("snapshot")
+                .help("Import a snapshot of the network state before syncing, so only the blocks after it have to be downloaded")
+                .value_name("FILE")
+                .requires("snapshot-key"))
+            .arg(Arg::with_name("snapshot-key")
+                .long("snapshot-key")
+                .help("PEM file with the public key of whoever is trusted to have signed the snapshot")
+                .value_name("FILE"))
         .group(ArgGroup::with_name("network"))
             .arg(Arg::with_name("hostname")
                 .long("host")
@@ -154,6 +201,27 @@ pub fn parse_cmdline<'a>() -> ArgMatches<'a> {
                 .help("Sets the interfaces which the JSONRPC interface should listen")
                 .default_value("127.0.0.1")
                 .value_name("HOST"))
+            .arg(Arg::with_name("rpcwsport")
+                .long("rpcwsport")
+                .help("Also serve the JSONRPC interface over WebSocket on this port, which enables subscriptions")
+                .value_name("PORT"))
+            .arg(Arg::with_name("rpcuser")
+                .long("rpcuser")
+                .help("Require HTTP basic auth with this user for privileged JSONRPC methods")
+                .requires("rpcpassword")
+                .value_name("USER"))
+            .arg(Arg::with_name("rpcpassword")
+                .long("rpcpassword")
+                .help("The password which goes with --rpcuser")
+                .requires("rpcuser")
+                .value_name("PASSWORD"))
+            .arg(Arg::with_name("rpctoken")
+                .long("rpctoken")
+                .help("Require this bearer token for privileged JSONRPC methods")
+                .value_name("TOKEN"))
+            .arg(Arg::with_name("rpccookie")
+                .long("rpccookie")
+                .help("Require the secret written to a cookie file in the storage directory for privileged JSONRPC methods"))
         
         // positional argument provided means to call rpc
         .arg(Arg::with_name("rpccmd")
@@ -215,6 +283,19 @@ pub fn make_genesis() -> (Block, Vec<Txn>) {
     (b, vec![txn])
 }
 
+/// Returns the shares which the validators registered in genesis start with, since they would
+/// never be able to forge without any. They are seeded by record keeper rather than being part of
+/// the genesis block so that the genesis hash stays the same.
+pub fn genesis_stake() -> Vec<(U160, u64)> {
+    let admkey = PKey::public_key_from_pem(ADMIN_KEY).unwrap()
+        .public_key_to_der().unwrap();
+
+    let testkey = PKey::private_key_from_pem(TESTING_PRIVATE).unwrap()
+        .public_key_to_der().unwrap();
+
+    vec![(hash_pub_key(&admkey), BLOCK_REWARD), (hash_pub_key(&testkey), BLOCK_REWARD)]
+}
+
 pub fn load_or_generate_key(name: &str) -> PKey {
     let key: PKey;
 
@@ -277,20 +358,55 @@ fn decode_bytes(s: &str) -> u64 {
     }.expect("Bytes must be a number, or end in the appropriate suffix (K, M, G, T)")
 }
 
-/// Generates the record keeper configuration from checkers game rules and the command line arguments
-pub fn make_rk_config(cmdline: &ArgMatches, cache: &game::GameCache) -> RecordKeeperConfig {
+/// Generates the record keeper configuration from checkers game rules and the command line arguments.
+/// Claims from other shards are checked against the header chains kept in `shard_headers`.
+pub fn make_rk_config(cmdline: &ArgMatches, cache: &game::GameCache, shard_headers: Arc<ShardHeaderIndex>) -> RecordKeeperConfig {
 
     let strategy = match cmdline.value_of_lossy("indexing").unwrap().as_ref() {
         "full" => RecordKeeperIndexingStrategy::Full,
         "standard" => RecordKeeperIndexingStrategy::Standard,
-        "light" => RecordKeeperIndexingStrategy::Light,
-        _ => panic!("Invalid indexing strategy (expected 'full', 'standard', or 'light'")
+        // clap refuses anything else, including 'light' which prunes the first moves games are rebuilt from
+        s => unreachable!("Invalid indexing strategy '{}'", s)
     };
 
     RecordKeeperConfig {
         pending_txn_limit: decode_bytes(&cmdline.value_of_lossy("mempool-size").unwrap()),
+        pending_txns_per_creator: cmdline.value_of("mempool-per-creator").unwrap().parse::<usize>().expect("Mempool per creator limit must be a number"),
+        pending_txn_ttl: cmdline.value_of("mempool-ttl").unwrap().parse::<u64>().expect("Mempool TTL must be a number"),
+        orphan_block_limit: decode_bytes(&cmdline.value_of_lossy("orphan-pool-size").unwrap()),
+        orphan_block_ttl: cmdline.value_of("orphan-ttl").unwrap().parse::<u64>().expect("Orphan TTL must be a number"),
         index_strategy: strategy,
+        finality_depth: cmdline.value_of("finality-depth").unwrap().parse::<u64>().expect("Finality depth must be a number"),
+        validators_scan: VALIDATORS_SCAN,
+        max_block_size: MAX_BLOCK_SIZE,
+        max_block_txns: MAX_BLOCK_TXNS,
+        block_rules: default_block_rules(),
+        txn_rules: default_txn_rules(),
         rules: rules::build_rules(Arc::clone(cache)),
+        shard_headers: Some(shard_headers),
+        genesis_stake: genesis_stake(),
+    }
+}
+
+/// Import the snapshot given on the command line, if any, so that syncing starts from it rather
+/// than genesis. Nothing happens if the database has already synced past genesis.
+pub fn import_snapshot(cmdline: &ArgMatches, rk: &RecordKeeper) {
+    let (path, key_path) = match (cmdline.value_of("snapshot"), cmdline.value_of("snapshot-key")) {
+        (Some(p), Some(k)) => (p, k),
+        _ => return
+    };
+
+    let mut raw = Vec::new();
+    File::open(path).and_then(|mut f| f.read_to_end(&mut raw)).expect("Could not read snapshot file");
+    let mut pem = Vec::new();
+    File::open(key_path).and_then(|mut f| f.read_to_end(&mut pem)).expect("Could not read snapshot key file");
+    let key = PKey::public_key_from_pem(&pem).expect("Invalid snapshot key");
+
+    let snapshot = Snapshot::decode(&raw).expect("Invalid snapshot file");
+    match rk.import_snapshot(snapshot, &key) {
+        Ok(head) => info!("Starting sync from snapshot at block {}", head),
+        Err(Error::Logic(LogicError::AlreadySynced)) => info!("Not importing snapshot since the database is already synced"),
+        Err(e) => panic!("Could not import snapshot: {}", e)
     }
 }
 
@@ -300,7 +416,40 @@ pub fn make_rpc(cmdline: &ArgMatches, ctx: Rc<Context>) -> RPC {
     let bind_addr = SocketAddr::new(cmdline.value_of("rpcbind").unwrap().parse().expect("Invalid RPC bind IP"), 
             cmdline.value_of("rpcport").unwrap().parse::<u16>().expect("Invalid RPC port: must be a number!"));
 
-    rpc::make_rpc(&ctx, bind_addr)
+    let ws_addr = cmdline.value_of("rpcwsport").map(|p| SocketAddr::new(bind_addr.ip(),
+            p.parse::<u16>().expect("Invalid WebSocket RPC port: must be a number!")));
+
+    let mut credentials = Vec::new();
+    if cmdline.is_present("rpccookie") {
+        let path = get_rpc_cookie_path().unwrap();
+        credentials.push(Credentials::create_cookie(&path).expect("Could not write RPC cookie file"));
+    }
+    if let (Some(user), Some(password)) = (cmdline.value_of("rpcuser"), cmdline.value_of("rpcpassword")) {
+        credentials.push(Credentials::Basic { user: user.into(), password: password.into() });
+    }
+    if let Some(token) = cmdline.value_of("rpctoken") {
+        credentials.push(Credentials::Bearer(token.into()));
+    }
+
+    if credentials.is_empty() && !bind_addr.ip().is_loopback() {
+        warn!("The JSONRPC interface is reachable from other hosts without authentication. Anyone who can reach {} may stop the node or sign with its keys.", bind_addr);
+    }
+
+    rpc::make_rpc(&ctx, bind_addr, ws_addr, AccessControl::new(credentials))
+}
+
+/// Pick the credentials to call RPC with: those given on the command line, otherwise the cookie of
+/// a node running with the same storage directory.
+fn rpc_credentials(cmdline: &ArgMatches) -> Option<Credentials> {
+    if let Some(token) = cmdline.value_of("rpctoken") {
+        Some(Credentials::Bearer(token.into()))
+    }
+    else if let (Some(user), Some(password)) = (cmdline.value_of("rpcuser"), cmdline.value_of("rpcpassword")) {
+        Some(Credentials::Basic { user: user.into(), password: password.into() })
+    }
+    else {
+        get_rpc_cookie_path().and_then(|p| Credentials::read_cookie(&p).ok())
+    }
 }
 
 pub fn call_rpc(cmdline: &ArgMatches) -> i32 {
@@ -331,7 +480,7 @@ pub fn call_rpc(cmdline: &ArgMatches) -> i32 {
     let bind_addr = SocketAddr::new(cmdline.value_of("rpcbind").unwrap().parse().expect("Invalid RPC bind IP"), 
             cmdline.value_of("rpcport").unwrap().parse::<u16>().expect("Invalid RPC port: must be a number!"));
 
-    let res = JsonRpcRequest::new(method, a).exec_sync(bind_addr);
+    let res = JsonRpcRequest::new(method, a).exec_sync(bind_addr, rpc_credentials(cmdline).as_ref());
 
     if res.is_err() {
         println!("RPC Error: {}", res.err().unwrap());
@@ -1,14 +1,14 @@
 use checkers;
 use std::sync::Arc;
 use parking_lot::RwLock;
-use blockscape_core::record_keeper::{RecordKeeper, GameStateCache, Error, PlotID, PlotEvent};
+use blockscape_core::record_keeper::{RecordKeeper, GameStateCache, GameState, GameEngine, GameEventCodec, Error, LogicError, PlotID, PlotEvent};
 use blockscape_core::primitives::{Txn, Mutation, Change, U160_ZERO, U160};
 use std::collections::BTreeSet;
 use blockscape_core::bin::*;
 use blockscape_core::time::Time;
 use blockscape_core::hash::hash_pub_key;
 use openssl::pkey::PKey;
-use bincode;
+use std::error::Error as StdErr;
 
 pub type GameCache = Arc<RwLock<GameStateCache<checkers::Board>>>;
 
@@ -19,10 +19,29 @@ pub fn create_cache() -> GameCache {
 }
 
 
+impl GameState for checkers::Board {
+    type Event = checkers::Event;
+
+    fn initial(_plot: PlotID) -> checkers::Board {
+        checkers::Board::default()
+    }
+
+    fn apply(&mut self, tick: u64, event: checkers::Event, _player: Option<U160>) -> Result<(), Error> {
+        // Start and Join only decide who is playing
+        if tick < 2 { return Ok(()); }
+
+        let player = checkers::Player::from_turn(tick)
+            .map_err(|e| LogicError::InvalidMutation(e.description().into()))?;
+        self.play(event, player)
+            .map_err(|e| LogicError::InvalidMutation(e.description().into()).into())
+    }
+}
+
+
 pub struct CheckersGame {
     pub rk: Arc<RecordKeeper>,
     pub sign_key: PKey,
-    pub cache: GameCache
+    pub engine: GameEngine<checkers::Board>
 }
 
 impl CheckersGame {
@@ -31,64 +50,30 @@ impl CheckersGame {
     /// the latest board. If it is None, it will simply get the latest known state. If no game has
     /// been started on the given plot, it will return the default starting board.
     pub fn get_board(&self, location: PlotID, tick: Option<u64>) -> Result<checkers::Board, Error> {
-        let (actual_tick, mut board) = self.cache.read().latest(location, tick)
-            .map(|(t, b)| (t, b.clone()))
-            .unwrap_or((0, checkers::Board::default()));
-        debug_assert!(!tick.is_some() || actual_tick <= tick.unwrap());
-
-        if !tick.is_some() || actual_tick < tick.unwrap() { return Ok(board); }
-
-        // update state
-        let tick = tick.unwrap();
-        let raw_events = self.rk.get_plot_events(location, 0)?;
-        for (actual_tick, raw_event_list) in raw_events {
-            if actual_tick == 0 { continue; }
-            else if actual_tick > tick { break; }
-            debug_assert_eq!(raw_event_list.len(), 1);
-            board.play(
-                bincode::deserialize(&raw_event_list[0])?,
-                checkers::Player::from_turn(actual_tick).unwrap()
-            ).unwrap();
-        } Ok(board)
+        self.engine.state_at(&*self.rk, location, tick)
     }
     
     /// Will return the actual players of the game at a given plot ID. If a slot is not filled,
     /// it will be marked by U160_ZERO
     pub fn get_players(&self, location: PlotID) -> Result<(U160, U160), Error> {
+        let codec = GameEventCodec::<checkers::Event>::new();
         let events = self.rk.get_plot_events(location, 0)?;
         
-        if !events.contains_key(&0) {
-            return Ok((U160_ZERO, U160_ZERO));
-        }
-        
-        if events.contains_key(&1) {
-            if let checkers::Event::Start(p1, p2) = bincode::deserialize(&events.get(&0).unwrap()[0])? {
-                if let checkers::Event::Join(player) = bincode::deserialize(&events.get(&1).unwrap()[0])? {
-                    if p1 == U160_ZERO {
-                        Ok((player, p2))
-                    }
-                    else if p2 == U160_ZERO {
-                        Ok((p1, player))
-                    }
-                    else {
-                        unreachable!();
-                    }
-                }
-                else {
-                    unreachable!();
-                }
-            }
-            else {
-                unreachable!();
-            }
-        }
-        else {
-            if let checkers::Event::Start(p1, p2) = bincode::deserialize(&events.get(&0).unwrap()[0])? {
-                Ok((p1, p2))
-            }
-            else {
-                unreachable!();
-            }
+        let (p1, p2) = match events.get(&0) {
+            Some(start) => match codec.decode_raw(&start[0])? {
+                checkers::Event::Start(p1, p2) => (p1, p2),
+                _ => return Err(unexpected_event("Start"))
+            },
+            None => return Ok((U160_ZERO, U160_ZERO))
+        };
+
+        match events.get(&1) {
+            Some(join) => match codec.decode_raw(&join[0])? {
+                checkers::Event::Join(player) if p1 == U160_ZERO => Ok((player, p2)),
+                checkers::Event::Join(player) if p2 == U160_ZERO => Ok((p1, player)),
+                _ => Err(unexpected_event("Join"))
+            },
+            None => Ok((p1, p2))
         }
     }
 
@@ -96,12 +81,13 @@ impl CheckersGame {
     /// tick). Will return an empty list if no game has been started on the given plot.
     /// NOTE: Does not include Start and Join events
     pub fn get_moves(&self, location: PlotID) -> Result<Vec<checkers::Event>, Error> {
+        let codec = GameEventCodec::<checkers::Event>::new();
         let raw_events = self.rk.get_plot_events(location, 2)?;
 
         let mut events = Vec::new();
         for (_, raw_event_list) in raw_events {
             debug_assert!(raw_event_list.len() == 1);
-            events.push(bincode::deserialize(&raw_event_list[0])?);
+            events.push(codec.decode_raw(&raw_event_list[0])?);
         } Ok(events)
     }
 
@@ -141,3 +127,9 @@ impl CheckersGame {
         Ok(())
     }
 }
+
+
+/// The error for a game whose events are not in the expected order.
+fn unexpected_event(expected: &str) -> Error {
+    LogicError::InvalidEvent(format!("Expected a {} event", expected)).into()
+}
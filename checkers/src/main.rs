@@ -46,9 +46,10 @@ use tokio_core::reactor::*;
 
 use blockscape_core::env;
 use blockscape_core::forging::epos::{EPoS, EPoSConfig};
+use blockscape_core::forging::slashing::DoubleSignDetector;
 use blockscape_core::network::client::*;
 use blockscape_core::network::ShardMode;
-use blockscape_core::record_keeper::{RecordKeeper, RecordKeeperImpl};
+use blockscape_core::record_keeper::{RecordKeeper, RecordKeeperImpl, GameEventCodec, GameEngine, GameStateCache};
 
 use game::CheckersGame;
 
@@ -87,14 +88,22 @@ fn main() {
     let genesis = make_genesis();
     let genesis_net = genesis.0.calculate_hash();
 
+    // the network keeps the header chains of other shards which record keeper checks claims against
+    let cc = make_network_config(&cmdline);
+
     let game_cache = game::create_cache();
     let rk: Arc<RecordKeeper> = Arc::new(
         RecordKeeperImpl::open(
             {let mut p = env::get_storage_dir().unwrap(); p.push("db"); p},
-            make_rk_config(&cmdline, &game_cache),
+            make_rk_config(&cmdline, &game_cache, Arc::clone(&cc.shard_headers)),
             genesis
         ).expect("Record Keeper was not able to initialize!")
     );
+    rk.register_event_codec(Arc::new(GameEventCodec::<checkers::Event>::new()));
+    boot::import_snapshot(&cmdline, &*rk);
+
+    // boards built on blocks which get reorganized away must be rebuilt
+    core.handle().spawn(GameStateCache::register_invalidator(Arc::clone(&game_cache), &*rk, 64));
 
     let mut threads: Vec<thread::JoinHandle<()>> = Vec::new();
     let (qs, qr) = channel::<()>();
@@ -102,11 +111,15 @@ fn main() {
     let quit = Box::new(qr).shared();
 
     // start network
-    let cc = make_network_config(&cmdline);
+    let shard_headers = Arc::clone(&cc.shard_headers);
     let (h, t) = Client::run(cc, Arc::clone(&rk), quit.clone()).expect("Could not start network");
 
     // must be connected to at least one network in order to do anything, might as well be genesis for now.
-    let net_client = h.send(ClientMsg::AttachNetwork(genesis_net, ShardMode::Primary)).wait().expect("Could not attach to root network!");
+    let (attach_tx, attach_rx) = channel();
+    let net_client = h.send(ClientMsg::AttachNetwork(genesis_net, ShardMode::Primary, attach_tx)).wait().expect("Could not attach to root network!");
+    if let Err(e) = attach_rx.wait().expect("Network client stopped before attaching to root network!") {
+        panic!("Could not attach to root network: {}", e);
+    }
     threads.push(t);
 
     let forge_key = boot::load_or_generate_key("forge");
@@ -114,7 +127,7 @@ fn main() {
     let checkers_game = Arc::new(CheckersGame{ 
         rk: Arc::clone(&rk), 
         sign_key: boot::load_or_generate_key("forge"), 
-        cache: game_cache 
+        engine: GameEngine::new(game_cache)
     });
 
     let ctx = Rc::new(Context {
@@ -125,9 +138,10 @@ fn main() {
         forge_algo: EPoS::new(rk, net_client, core.handle().remote().clone(), EPoSConfig {
                 rate_target: 12 * 1000, // 12 seconds
                 recalculate_blocks: 1800, // 6 hours 
-                validators_scan: 100,
+                validators_scan: boot::VALIDATORS_SCAN,
                 validators_count_base: 3,
-                signing_keys: vec![forge_key.private_key_to_der().unwrap()]
+                signing_keys: vec![forge_key.private_key_to_der().unwrap()],
+                shard_headers: Some(shard_headers)
             }).expect("Could not start the proof of stake forger!"),
 
         forge_key: forge_key
@@ -160,6 +174,10 @@ fn main() {
     //     .map_err(|_| ());
 
     core.handle().spawn(rpt_job);
+
+    // report validators who sign competing blocks
+    DoubleSignDetector::new(Arc::clone(&ctx.rk), boot::load_or_generate_key("forge"), VALIDATORS_SCAN)
+        .watch(&handler);
     // core.handle().spawn(test_txn_job);
 
     if cmdline.is_present("forge") {
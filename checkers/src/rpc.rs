@@ -21,18 +21,36 @@ use game::CheckersGame;
 use context::Context;
 use checkers;
 
-pub fn make_rpc(ctx: &Rc<Context>, bind_addr: SocketAddr) -> RPC {
-
-    let mut handler = RPC::build_handler();
-
-    ControlRPC::add(&ControlRPC::new(), &mut handler);
-    NetworkRPC::add(&NetworkRPC::new(ctx.network.clone()), &mut handler);
+pub fn make_rpc(ctx: &Rc<Context>, bind_addr: SocketAddr, ws_addr: Option<SocketAddr>, access: AccessControl) -> RPC {
 
+    let control = ControlRPC::new();
+    let network = NetworkRPC::new(ctx.network.clone());
     let forge_key = PKey::private_key_from_der(&ctx.forge_key.private_key_to_der().unwrap()).unwrap();
-    BlockchainRPC::add(&BlockchainRPC::new(ctx.rk.clone(), forge_key), &mut handler);
-    CheckersRPC::add(&CheckersRPC::new(ctx.game.clone(), PKey::private_key_from_der(&ctx.forge_key.private_key_to_der().unwrap()).unwrap()), &mut handler);
-
-    RPC::run(bind_addr, handler)
+    let blockchain = BlockchainRPC::new(ctx.rk.clone(), forge_key);
+    let checkers = CheckersRPC::new(ctx.game.clone(), PKey::private_key_from_der(&ctx.forge_key.private_key_to_der().unwrap()).unwrap());
+
+    access.register::<ControlRPC>();
+    access.register::<NetworkRPC>();
+    access.register::<BlockchainRPC>();
+    access.register::<CheckersRPC>();
+    let access = Arc::new(access);
+
+    let build_handler = || {
+        let mut handler = RPC::build_handler(&access);
+        ControlRPC::add(&control, &mut handler);
+        NetworkRPC::add(&network, &mut handler);
+        BlockchainRPC::add(&blockchain, &mut handler);
+        CheckersRPC::add(&checkers, &mut handler);
+        handler
+    };
+
+    let rpc = RPC::run(bind_addr, build_handler(), Arc::clone(&access));
+    if let Some(ws_addr) = ws_addr {
+        // subscriptions need a session, so they are only offered over WebSocket
+        let mut handler = build_handler();
+        SubscriptionRPC::add(&SubscriptionRPC::new(&*ctx.rk), &mut handler, &access);
+        rpc.with_ws(ws_addr, handler)
+    } else { rpc }
 }
 
 pub struct CheckersRPC {
@@ -54,6 +72,10 @@ impl RPCHandler for CheckersRPC {
 
         io.extend_with(d);
     }
+
+    fn read_only_methods() -> &'static [&'static str] {
+        &["get_checkers_board", "get_my_player"]
+    }
 }
 
 impl CheckersRPC {
@@ -30,6 +30,14 @@ pub fn get_storage_dir() -> Option<PathBuf> {
     }
 }
 
+/// Path of the cookie file which local RPC clients read to authenticate with the node.
+pub fn get_rpc_cookie_path() -> Option<PathBuf> {
+    get_storage_dir().map(|mut d| {
+        d.push(".cookie");
+        d
+    })
+}
+
 /// Return the formal name of this executable.
 pub fn get_client_name() -> String {
     // TODO: Make more intelligent
@@ -0,0 +1,54 @@
+use openssl::pkey::PKey;
+
+use bin::Bin;
+
+use forging::ForgeError;
+use hash::hash_pub_key;
+use primitives::{BlockHeader, Change, Mutation, Txn};
+use record_keeper::RecordKeeper;
+use record_keeper::mutation::ShardHeaders;
+
+/// Number of blocks a child shard must move forward before its head is anchored again.
+pub const CHILD_REF_INTERVAL: u64 = 10;
+
+/// Create a txn anchoring the head of every child shard which has moved at least
+/// `CHILD_REF_INTERVAL` blocks on its header chain since it was last anchored. The references all
+/// go into one txn so they only take up a single slot in the block. Returns `None` if no child
+/// shard needs to be anchored.
+///
+/// Like the block reward, the txn is stamped with the time of `prev`, the block being built on, so
+/// forging again on top of it makes the same txn as long as the child heads have not moved.
+pub fn make_child_refs_txn(rk: &RecordKeeper, headers: &ShardHeaders, prev: &BlockHeader, key: &PKey) -> Result<Option<Txn>, ForgeError> {
+    let children = rk.get_child_shards()
+        .map_err(|e| ForgeError(format!("Could not look up child shards: {}", e)))?;
+
+    let mut mutation = Mutation::new();
+    for (shard, _) in children {
+        let (block, height) = match headers.get_head(&shard) {
+            Some(head) => head,
+            None => continue // we are not following this shard
+        };
+
+        let last = rk.get_child_block_ref(&shard)
+            .map_err(|e| ForgeError(format!("Could not look up child block reference: {}", e)))?
+            .map_or(0, |r| r.1);
+
+        if height >= last + CHILD_REF_INTERVAL {
+            mutation.changes.push(Change::ChildBlockRef { shard, block, height });
+        }
+    }
+
+    if mutation.changes.is_empty() {
+        return Ok(None);
+    }
+
+    let der = key.public_key_to_der()
+        .map_err(|_| ForgeError(format!("Could not convert public key to DER")))?;
+    let txn = Txn {
+        timestamp: prev.timestamp,
+        creator: hash_pub_key(&der),
+        mutation,
+        signature: Bin::new()
+    };
+    Ok(Some(txn.sign(key)))
+}
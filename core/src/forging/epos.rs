@@ -12,8 +12,10 @@ use crypto::sha3::Sha3;
 use crypto::digest::Digest;
 use openssl::pkey::PKey;
 
-use forging::{BlockForger, ForgeError};
+use forging::{BlockForger, HeaderVerifier, ForgeError};
+use forging::{anchoring, rewards};
 use record_keeper::RecordKeeper;
+use record_keeper::mutation::ShardHeaders;
 use network::client::BroadcastReceiver;
 use network::client::ClientMsg;
 use primitives::block::{Block, BlockHeader};
@@ -46,7 +48,11 @@ pub struct EPoSConfig {
     //validator_cooldown: u64,
 
     /// Signing private key(s) for us to participate in the forge
-    pub signing_keys: Vec<Vec<u8>>
+    pub signing_keys: Vec<Vec<u8>>,
+
+    /// Headers of the child shards, used to anchor their heads into the blocks we forge. Should be
+    /// the same as the `shard_headers` given to record keeper.
+    pub shard_headers: Option<Arc<ShardHeaders>>
 }
 
 impl EPoSConfig {
@@ -57,7 +63,8 @@ impl EPoSConfig {
             recalculate_blocks: 1800, // 6 hours 
             validators_scan: 100,
             validators_count_base: 3,
-            signing_keys
+            signing_keys,
+            shard_headers: None
         }
     }
 }
@@ -138,6 +145,102 @@ impl EPoSBlockData {
     }
 }
 
+/// Calculates the validator target hash for a block following `prev`, along with the number of
+/// distinct validators seen in the `validators_scan` blocks before it. Headers are looked up with
+/// `get_header` so this can be run against any view of the chain.
+pub fn calculate_validator_target<F>(prev: &U256, validators_scan: u64, mut get_header: F) -> Result<(U256, usize), ForgeError>
+    where F: FnMut(&U256) -> Result<BlockHeader, ForgeError>
+{
+    // First, update the validator hashes (we only look at the MIDDLE one in each block since it is the hardest to grind)
+    let mut blocks = Vec::with_capacity(validators_scan as usize);
+    {
+        let mut p = *prev;
+        for _ in 0..validators_scan {
+            blocks.push(get_header(&p)?);
+
+            p = blocks[blocks.len() - 1].prev;
+
+            if p == U256_ZERO {
+                blocks.pop(); // we do not want to include genesis itself
+                break; // we cannot go back any further, so also we have no need to continue.
+            }
+        }
+    }
+
+    let mut validators: HashSet<U160> = HashSet::new();
+
+    let mut buf = [0u8; 32];
+    let mut hasher = Sha3::sha3_256();
+
+    for block in blocks {
+        let (validator_id, sig) = EPoSBlockData::decode_relevant_validation_data(&block)?;
+
+        sig.to_big_endian(&mut buf);
+        hasher.input(&buf);
+
+        validators.insert(validator_id);
+    }
+
+    hasher.result(&mut buf); //don't care about first hash, only the second
+    hasher.reset();
+    hasher.input(&buf);
+    hasher.result(&mut buf);
+
+    Ok((U256::from_big_endian(&mut buf), validators.len()))
+}
+
+/// The number of validator signatures required on a block given how many distinct validators have
+/// been active recently.
+pub fn required_validators(active: usize, validators_count_base: u64) -> u64 {
+    max((max(active, 1) as f64).log(validators_count_base as f64).trunc() as u64, 1)
+}
+
+/// Calculates the difficulty expected of a block at `height` which follows `prev`. Headers are
+/// looked up with `get_header` so this can be run against any view of the chain.
+pub fn expected_difficulty<F>(prev: &U256, height: u64, rate_target: u64, recalculate_blocks: u64, mut get_header: F) -> Result<u64, ForgeError>
+    where F: FnMut(&U256) -> Result<BlockHeader, ForgeError>
+{
+    let decode = |header: &BlockHeader| bincode::deserialize::<EPoSBlockData>(&header.blob[..])
+        .map(|d| d.difficulty)
+        .map_err(|e| ForgeError(format!("Could not deserialize block blob (buffer size was {}): {}", header.blob.len(), e)));
+
+    if height % recalculate_blocks != 0 {
+        if height < recalculate_blocks {
+            // use the starting difficulty
+            return Ok(1);
+        }
+
+        return decode(&get_header(prev)?);
+    }
+
+    debug!("Recalculating difficulty!");
+
+    let mut n = recalculate_blocks;
+    if height == recalculate_blocks {
+        // we dont want to walk all the way back to genesis
+        n -= 2;
+    }
+
+    // the best way to find this block is to walk back recalculate_blocks
+    let mut hash_cur = *prev;
+    let pb = get_header(&hash_cur)?;
+
+    for _ in 1..n {
+        hash_cur = get_header(&hash_cur)?.prev;
+    }
+
+    // how long *should* it have taken to get to this point?
+    let expected = rate_target * n;
+
+    let b = get_header(&hash_cur)?;
+    let actual = b.timestamp.diff(&pb.timestamp).millis() as u64;
+    let last_diff = decode(&pb)?;
+
+    debug!("Expected: {}, Actual: {}, Last Diff: {}", expected, actual, last_diff);
+
+    Ok((expected * last_diff / actual) as u64)
+}
+
 struct EPoSContext {
     /// A reference to RecordKeeper so block generation/preparation can happen
     pub rk: Arc<RecordKeeper>,
@@ -191,6 +294,7 @@ impl EPoS {
 
         // register ourself
         pos.ctx.net.unbounded_send(ClientMsg::RegisterBroadcastReceiver(EPOS_BROADCAST_ID, pos2)).expect("Could not register EPoS with network");
+        pos.ctx.net.unbounded_send(ClientMsg::RegisterHeaderVerifier(Arc::new(EPoSHeaderVerifier::new(&pos.config)))).expect("Could not register EPoS with network");
 
         Ok(pos)
     }
@@ -272,102 +376,83 @@ impl EPoS {
 
     /// Calculates the validator target hash, and the number of validators required to validate
     fn calculate_validator_info(&self, prev: &U256) -> Result<(U256, u64), ForgeError> {
-        // First, update the validator hashes (we only look at the MIDDLE one in each block since it is the hardest to grind)
-        let mut blocks = Vec::with_capacity(self.config.validators_scan as usize);
-        {
-            let mut p = *prev;
-            for _ in 0..self.config.validators_scan {
-                blocks.push(self.ctx.rk.get_block_header(&p).map_err(|e| ForgeError(format!("Could not get a block from db: {}", e).into()))?);
-
-                p = blocks[blocks.len() - 1].prev;
-
-                if p == U256_ZERO {
-                    blocks.pop(); // we do not want to include genesis itself
-                    break; // we cannot go back any further, so also we have no need to continue.
-                }
-            }
-        }
-
-        let mut validators: HashSet<U160> = HashSet::new();
-
-        let mut buf = [0u8; 32];
-        let mut hasher = Sha3::sha3_256();
-
-        for block in blocks {
-            let (validator_id, sig) = EPoSBlockData::decode_relevant_validation_data(&block)?;
-
-            sig.to_big_endian(&mut buf);
-            hasher.input(&buf);
-
-            validators.insert(validator_id);
-        }
+        let (target, validators) = calculate_validator_target(prev, self.config.validators_scan, |h|
+            self.ctx.rk.get_block_header(h).map_err(|e| ForgeError(format!("Could not get a block from db: {}", e).into()))
+        )?;
 
-        hasher.result(&mut buf); //don't care about first hash, only the second
-        hasher.reset();
-        hasher.input(&buf);
-        hasher.result(&mut buf);
-
-        Ok((U256::from_big_endian(&mut buf), max((max(validators.len(), 1) as f64).log(self.config.validators_count_base as f64).trunc() as u64, 1)))
+        Ok((target, required_validators(validators, self.config.validators_count_base)))
     }
 
     /// Calculates the actual block difficulty, taking into account the current level of validators required and etc.
     fn calculate_expected_difficulty(&self, block: &Block) -> Result<u64, ForgeError> {
         let height = try!(self.ctx.rk.get_block_height(&block.header.prev)
             .map_err(|e| ForgeError(format!("Could not get a block height: {}", e).into()))) + 1;
-        
-        let base_diff = if height % self.config.recalculate_blocks != 0 {
 
-            
-            if height < self.config.recalculate_blocks {
-                // use the starting difficulty
-                Ok(1)
-            }
-            else {
+        expected_difficulty(&block.header.prev, height, self.config.rate_target, self.config.recalculate_blocks, |h|
+            self.ctx.rk.get_block_header(h).map_err(|e| ForgeError(format!("Could not get a block from db: {}", e).into()))
+        )
+    }
 
-                let block_data = bincode::deserialize::<EPoSBlockData>(
-                    &try!(self.ctx.rk.get_block(&block.header.prev)
-                        .map_err(|e| ForgeError(format!("Could not get a block from db: {}", e).into()))).header.blob).unwrap();
-                
-                Ok(block_data.difficulty)
-            }
+    /// Include a txn paying the signers of the previous block their reward. The txn is put into the
+    /// pending pool (without being broadcast) so the block can be accepted with it. Every attempt
+    /// on the same previous block makes the same txn, so a block never carries two rewards.
+    fn add_reward(&self, mut block: Block) -> Block {
+        if self.keys.is_empty() {
+            return block; // we will not be signing this anyway
         }
-        else {
-
-            debug!("Recalculating difficulty!");
-
-            let mut n = self.config.recalculate_blocks;
-            if height == self.config.recalculate_blocks {
-                // we dont want to walk all the way back to genesis
-                n -= 2;
-            }
 
-            // the best way to find this block is to walk back recalculate_blocks
-            let mut hash_cur = block.header.prev;
-
-            let pb = try!(self.ctx.rk.get_block(&hash_cur)
-                    .map_err(|e| ForgeError(format!("Could not get a block from db: {}", e).into())));
-            
-            for _ in 1..n {
-                hash_cur = try!(self.ctx.rk.get_block(&hash_cur)
-                    .map_err(|e| ForgeError(format!("Could not get a block from db: {}", e).into()))).header.prev;
-            }
-
-            // how long *should* it have taken to get to this point?
-            let expected = self.config.rate_target * n;
-
-            let b = try!(self.ctx.rk.get_block(&hash_cur)
-                    .map_err(|e| ForgeError(format!("Could not get a block from db: {}", e).into())));
+        let prev = match self.ctx.rk.get_block_header(&block.prev) {
+            Ok(h) => h,
+            Err(e) => { warn!("Could not get previous block to reward: {:?}", e); return block; }
+        };
 
-            let actual = b.header.timestamp.diff(&pb.header.timestamp).millis() as u64;
+        match rewards::make_reward_txn(&prev, &self.keys[0].1) {
+            Ok(Some(txn)) => {
+                let hash = txn.calculate_hash();
+                match self.ctx.rk.add_pending_txn(txn, false) {
+                    Ok(_) => if !block.txns.contains(&hash) {
+                        block.txns.push(hash);
+                        block.header.merkle_root = Block::calculate_merkle_root(&block.txns);
+                    },
+                    Err(e) => warn!("Block reward txn was rejected: {:?}", e)
+                }
+            },
+            Ok(None) => {},
+            Err(e) => warn!("Could not create block reward txn: {:?}", e)
+        }
 
-            let last_diff = bincode::deserialize::<EPoSBlockData>(&pb.header.blob).unwrap().difficulty;
+        block
+    }
 
-            debug!("Expected: {}, Actual: {}, Last Diff: {}", expected, actual, last_diff);
+    /// Include a txn anchoring the heads of child shards which have moved forward. Like the reward,
+    /// the txn is put into the pending pool (without being broadcast).
+    fn add_child_refs(&self, mut block: Block) -> Block {
+        let headers = match self.config.shard_headers {
+            Some(ref h) if !self.keys.is_empty() => h,
+            _ => return block
+        };
 
-            Ok((expected * last_diff / actual) as u64)
+        let prev = match self.ctx.rk.get_block_header(&block.prev) {
+            Ok(h) => h,
+            Err(e) => { warn!("Could not get previous block to anchor child shards: {:?}", e); return block; }
         };
 
-        base_diff
+        match anchoring::make_child_refs_txn(&*self.ctx.rk, &**headers, &prev, &self.keys[0].1) {
+            Ok(Some(txn)) => {
+                let hash = txn.calculate_hash();
+                match self.ctx.rk.add_pending_txn(txn, false) {
+                    Ok(_) => if !block.txns.contains(&hash) {
+                        block.txns.push(hash);
+                        block.header.merkle_root = Block::calculate_merkle_root(&block.txns);
+                    },
+                    Err(e) => warn!("Child block reference txn was rejected: {:?}", e)
+                }
+            },
+            Ok(None) => {},
+            Err(e) => warn!("Could not create child block reference txn: {:?}", e)
+        }
+
+        block
     }
 
     /// Tries to add our own signature to a received block, and prepare it for transmission if it is a keeper.
@@ -541,6 +626,7 @@ impl BlockForger for EPoS {
         let (tx, rx) = oneshot::channel();
         *self.ctx.on_block.lock().unwrap() = Some(tx);
 
+        let block = self.add_child_refs(self.add_reward(block));
         self.evaluate_block(block);
         Box::new(rx.map_err(|_| ForgeError(format!("Cancelled forge!"))))
     }
@@ -615,6 +701,54 @@ impl BlockForger for EPoS {
     }
 }
 
+/// Verifies EPoS blocks from their headers alone, for nodes which do not keep the network state
+/// (i.e. `ShardMode::QueryOnly`). Validator stakes are not known, so unlike `EPoS::validate` the
+/// forging wait implied by the timestamp cannot be checked.
+pub struct EPoSHeaderVerifier {
+    rate_target: u64,
+    recalculate_blocks: u64,
+    validators_scan: u64,
+    validators_count_base: u64
+}
+
+impl EPoSHeaderVerifier {
+    pub fn new(config: &EPoSConfig) -> EPoSHeaderVerifier {
+        EPoSHeaderVerifier {
+            rate_target: config.rate_target,
+            recalculate_blocks: config.recalculate_blocks,
+            validators_scan: config.validators_scan,
+            validators_count_base: config.validators_count_base
+        }
+    }
+}
+
+impl HeaderVerifier for EPoSHeaderVerifier {
+    fn verify_header(&self, header: &BlockHeader, height: u64, get_header: &Fn(&U256) -> Result<BlockHeader, ForgeError>) -> Result<(), ForgeError> {
+        let b_data = bincode::deserialize::<EPoSBlockData>(&header.blob[..])
+            .map_err(|_| ForgeError(format!("Block blob decode error!")))?;
+
+        let diff = expected_difficulty(&header.prev, height, self.rate_target, self.recalculate_blocks, |h| get_header(h))?;
+        if b_data.difficulty != diff {
+            return Err(ForgeError(format!("Block difficulty is invalid")));
+        }
+
+        let (target, validators) = calculate_validator_target(&header.prev, self.validators_scan, |h| get_header(h))?;
+        if b_data.sigs.len() as u64 != required_validators(validators, self.validators_count_base) {
+            return Err(ForgeError(format!("Signature count does not match expected")));
+        }
+
+        if !b_data.check_sigs(&target) {
+            return Err(ForgeError(format!("Block signatures do not line up!")));
+        }
+
+        if header.timestamp > Time::current() {
+            return Err(ForgeError(format!("Block has been submitted too early")));
+        }
+
+        Ok(())
+    }
+}
+
 impl BroadcastReceiver for EPoS {
     /// Returns a unique identifier to separate events for this broadcast ID. Must be unique per application.
     fn get_broadcast_id(&self) -> u8 {
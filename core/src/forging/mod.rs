@@ -1,5 +1,8 @@
 pub mod flower_picking;
 pub mod epos;
+pub mod anchoring;
+pub mod rewards;
+pub mod slashing;
 
 use std::error::Error;
 use std::fmt::Display;
@@ -7,10 +10,10 @@ use std::fmt;
 
 use futures::prelude::*;
 
-use primitives::Block;
+use primitives::{Block, BlockHeader, U256};
 
 #[derive(Debug)]
-pub struct ForgeError(String);
+pub struct ForgeError(pub String);
 
 impl Display for ForgeError {
     /// Print the error
@@ -36,4 +39,12 @@ impl Error for ForgeError {
 pub trait BlockForger {
     fn create(&self, block: Block) -> Box<Future<Item=Block, Error=ForgeError>>;
     fn validate(&self, block: &Block) -> Option<ForgeError>;
+}
+
+/// Checks the forging data of block headers without access to txns or the network state. Used by
+/// light clients which only follow the chain of headers.
+pub trait HeaderVerifier: Send + Sync {
+    /// Return Ok if the header at `height` is valid. Earlier headers in the chain can be looked up
+    /// with `get_header`.
+    fn verify_header(&self, header: &BlockHeader, height: u64, get_header: &Fn(&U256) -> Result<BlockHeader, ForgeError>) -> Result<(), ForgeError>;
 }
\ No newline at end of file
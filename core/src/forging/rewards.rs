@@ -0,0 +1,150 @@
+use std::collections::HashSet;
+use bincode;
+use openssl::pkey::PKey;
+
+use bin::{Bin, AsBin};
+use forging::ForgeError;
+use forging::epos::EPoSBlockData;
+use hash::hash_pub_key;
+use primitives::{BlockHeader, Change, Mutation, Txn, U160, U256};
+use record_keeper::{Error, LogicError, DBState, Database, MutationRule};
+
+/// Get the unique IDs of the validators who signed a block.
+fn signers(header: &BlockHeader) -> Result<Vec<U160>, ForgeError> {
+    let block_data = bincode::deserialize::<EPoSBlockData>(&header.blob[..])
+        .map_err(|e| ForgeError(format!("Could not deserialize block blob (buffer size was {}): {}", header.blob.len(), e)))?;
+
+    let mut seen = HashSet::new();
+    Ok(block_data.sigs.iter()
+        .map(|s| hash_pub_key(&s.0))
+        .filter(|id| seen.insert(*id))
+        .collect())
+}
+
+/// Create a txn paying `BLOCK_REWARD` to each validator who signed `prev`. Rewards are always paid
+/// out in the block directly following the one which was signed, since the signatures are not known
+/// until the block is complete. Returns `None` if there is nobody to reward (e.g. genesis).
+///
+/// The txn is stamped with the time of `prev` rather than the current time, so every attempt to
+/// forge on top of `prev` makes the very same txn instead of another reward for the same block.
+pub fn make_reward_txn(prev: &BlockHeader, key: &PKey) -> Result<Option<Txn>, ForgeError> {
+    let ids = signers(prev)?;
+    if ids.is_empty() {
+        return Ok(None);
+    }
+
+    let proof = prev.calculate_hash().as_bin();
+    let mut mutation = Mutation::new();
+    for id in ids {
+        mutation.changes.push(Change::BlockReward { id, proof: proof.clone() });
+    }
+
+    let der = key.public_key_to_der()
+        .map_err(|_| ForgeError(format!("Could not convert public key to DER")))?;
+    let txn = Txn {
+        timestamp: prev.timestamp,
+        creator: hash_pub_key(&der),
+        mutation,
+        signature: Bin::new()
+    };
+    Ok(Some(txn.sign(key)))
+}
+
+
+/// Block rewards may only be paid to the validators who signed the block the state is currently at,
+/// once each, and must name that block as their proof. The amount is always `BLOCK_REWARD`.
+pub struct EPoSRewards;
+impl MutationRule for EPoSRewards {
+    fn is_valid(&self, state: &DBState, mutation: &Vec<(Change, U160)>, _cache: &mut Bin) -> Result<(), Error> {
+        let head = state.get_current_block_hash();
+        let mut allowed: Option<HashSet<U160>> = None;
+        let mut rewarded = HashSet::new();
+
+        for &(ref change, _) in mutation {
+            if let &Change::BlockReward { ref id, ref proof } = change {
+                if bincode::deserialize::<U256>(&proof[..]).ok() != Some(head) {
+                    return Err(LogicError::InvalidMutation("Block rewards must be for the previous block".into()).into());
+                }
+
+                if allowed.is_none() {
+                    let header = state.get_block_header(&head)?;
+                    let ids = signers(&header)
+                        .map_err(|e| LogicError::InvalidMutation(format!("Could not read block signers: {}", e)))?;
+                    allowed = Some(ids.into_iter().collect());
+                }
+
+                if !allowed.as_ref().unwrap().contains(id) {
+                    return Err(LogicError::InvalidMutation("Block reward paid to a validator who did not sign the block".into()).into());
+                }
+
+                if !rewarded.insert(*id) {
+                    return Err(LogicError::Duplicate.into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn description(&self) -> &'static str {
+        "Block rewards must only be paid once to each signer of the previous block."
+    }
+}
+
+
+#[test]
+fn block_rewards() {
+    use std::collections::BTreeSet;
+    use forging::epos::calculate_validator_target;
+    use primitives::Coord;
+    use record_keeper::{RecordKeeper, PlotEvent, test_record_keeper};
+    use record_keeper::database::BLOCK_REWARD;
+    use signer::generate_private_key;
+
+    let (key, other) = (generate_private_key(), generate_private_key());
+    let id = hash_pub_key(&key.public_key_to_der().unwrap());
+    let rk = test_record_keeper(&key, 1 << 20, 1, 3600);
+    let genesis = rk.get_current_block_hash();
+
+    // the validator signs the next block
+    let (target, _) = calculate_validator_target(&genesis, 100, |h|
+        rk.get_block_header(h).map_err(|e| ForgeError(format!("{:?}", e)))
+    ).unwrap();
+    let mut signed = rk.create_block().unwrap();
+    EPoSBlockData::apply_block(&mut signed, 1, &target, &key).unwrap();
+    assert!(rk.add_block(&signed, true).unwrap());
+
+    // the validator's own pending txns do not count against its reward
+    let mut mutation = Mutation::new();
+    mutation.changes.push(Change::PlotEvent(PlotEvent { from: Coord(0, 0), to: BTreeSet::new(), tick: 1, event: Bin::new() }));
+    assert!(rk.add_pending_txn(Txn::new(id, mutation).sign(&key), true).unwrap());
+
+    // forging again on the same block makes the same reward
+    let reward = make_reward_txn(&signed.header, &key).unwrap().unwrap();
+    assert_eq!(make_reward_txn(&signed.header, &key).unwrap().unwrap().calculate_hash(), reward.calculate_hash());
+    assert!(rk.add_pending_txn(reward.clone(), true).unwrap());
+    assert!(!rk.add_pending_txn(reward, true).unwrap());
+
+    let txn = |to, proof| {
+        let mut mutation = Mutation::new();
+        mutation.changes.push(Change::BlockReward { id: to, proof });
+        Txn::new(id, mutation).sign(&key)
+    };
+    let proof = signed.calculate_hash().as_bin();
+    let other_id = hash_pub_key(&other.public_key_to_der().unwrap());
+
+    match rk.add_pending_txn(txn(id, proof.clone()), true) {
+        Err(Error::Logic(LogicError::Duplicate)) => (),
+        r => panic!("Expected a second reward to be rejected, got {:?}", r)
+    }
+    for bad in vec![txn(id, Bin::new()), txn(id, genesis.as_bin()), txn(other_id, proof)] {
+        match rk.add_pending_txn(bad, true) {
+            Err(Error::Logic(LogicError::InvalidMutation(_))) => (),
+            r => panic!("Expected a reward with a missing or wrong proof to be rejected, got {:?}", r)
+        }
+    }
+
+    let block = rk.create_block().unwrap();
+    assert!(rk.add_block(&block, true).unwrap());
+    assert_eq!(rk.get_validator_stake(&id).unwrap(), BLOCK_REWARD * 2);
+}
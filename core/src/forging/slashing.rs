@@ -0,0 +1,261 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use bincode;
+use futures::prelude::*;
+use futures::sync::mpsc;
+use openssl::pkey::PKey;
+use tokio_core::reactor::Handle;
+
+use bin::Bin;
+use forging::ForgeError;
+use forging::epos::{EPoSBlockData, calculate_validator_target};
+use hash::hash_pub_key;
+use primitives::{BlockHeader, Change, Mutation, Txn, U160, U256};
+use record_keeper::{Error, LogicError, DBState, Database, MutationRule, RecordKeeper, RecordEvent, map_not_found};
+
+/// Evidence that a validator signed two competing blocks at the same height. This is serialized
+/// into the `proof` of a `Change::Slash`. The block with the lower hash comes first so each pair of
+/// blocks has only one valid encoding and cannot be used to slash twice.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DoubleSignProof {
+    pub first: BlockHeader,
+    pub second: BlockHeader
+}
+
+impl DoubleSignProof {
+    /// Create a proof from two headers in either order.
+    pub fn new(a: BlockHeader, b: BlockHeader) -> DoubleSignProof {
+        if a.calculate_hash() <= b.calculate_hash() {
+            DoubleSignProof { first: a, second: b }
+        } else {
+            DoubleSignProof { first: b, second: a }
+        }
+    }
+
+    pub fn decode(proof: &Bin) -> Result<DoubleSignProof, ForgeError> {
+        bincode::deserialize(&proof[..])
+            .map_err(|e| ForgeError(format!("Could not deserialize slash proof: {}", e)))
+    }
+
+    pub fn encode(&self) -> Bin {
+        bincode::serialize(self, bincode::Infinite).unwrap().into()
+    }
+}
+
+/// Find the signature of a given validator in the EPoS data of a block header, if they signed it.
+fn find_signer(header: &BlockHeader, id: &U160) -> Result<Option<EPoSBlockData>, ForgeError> {
+    let block_data = bincode::deserialize::<EPoSBlockData>(&header.blob[..])
+        .map_err(|e| ForgeError(format!("Could not deserialize block blob (buffer size was {}): {}", header.blob.len(), e)))?;
+
+    if block_data.sigs.iter().any(|s| hash_pub_key(&s.0) == *id) {
+        Ok(Some(block_data))
+    } else {
+        Ok(None)
+    }
+}
+
+
+/// Slashes must carry a `DoubleSignProof` showing the validator signed two different blocks at the
+/// same height, and both signature chains must verify against the network state. A proof may only
+/// be used once and cannot take more than the validator has staked.
+pub struct SlashEvidence {
+    /// Must match the `validators_scan` of the EPoS configuration in use
+    pub validators_scan: u64
+}
+
+impl SlashEvidence {
+    pub fn new(validators_scan: u64) -> SlashEvidence {
+        SlashEvidence { validators_scan }
+    }
+
+    /// Check that `header` contains a valid signature from `id`.
+    fn is_signed_by(&self, state: &DBState, header: &BlockHeader, id: &U160) -> Result<(), Error> {
+        let invalid = |e: ForgeError| LogicError::InvalidMutation(format!("Invalid slash proof: {}", e));
+
+        let block_data = find_signer(header, id).map_err(&invalid)?
+            .ok_or_else(|| LogicError::InvalidMutation("Slashed validator did not sign the block".into()))?;
+
+        let (target, _) = calculate_validator_target(&header.prev, self.validators_scan, |h|
+            state.get_block_header(h).map_err(|e| ForgeError(format!("Could not get a block from db: {}", e)))
+        ).map_err(&invalid)?;
+
+        if block_data.check_sigs(&target) { Ok(()) }
+        else { Err(LogicError::InvalidSignature.into()) }
+    }
+}
+
+impl MutationRule for SlashEvidence {
+    fn is_valid(&self, state: &DBState, mutation: &Vec<(Change, U160)>, _cache: &mut Bin) -> Result<(), Error> {
+        let mut used = HashSet::new();
+        let mut slashed: HashMap<U160, u64> = HashMap::new();
+
+        for &(ref change, _) in mutation {
+            if let &Change::Slash { ref id, amount, ref proof } = change {
+                if !used.insert((*id, proof)) || state.is_slash_evidence_used(id, proof)? {
+                    return Err(LogicError::Duplicate.into());
+                }
+
+                let total = slashed.entry(*id).or_insert(0);
+                *total = total.checked_add(amount).ok_or(LogicError::NotEnoughShares)?;
+                if amount == 0 || *total > map_not_found(state.get_validator_stake(*id), 0)? {
+                    return Err(LogicError::InvalidMutation("Slash amount must be more than zero and no more than the validator's stake".into()).into());
+                }
+
+                let proof = DoubleSignProof::decode(proof)
+                    .map_err(|e| LogicError::InvalidMutation(format!("Invalid slash proof: {}", e)))?;
+
+                if proof.first.calculate_hash() >= proof.second.calculate_hash() {
+                    return Err(LogicError::InvalidMutation("Slash proof must contain two different blocks, lowest hash first".into()).into());
+                }
+
+                // blocks are at the same height iff their parents are
+                if state.get_block_height(proof.first.prev)? != state.get_block_height(proof.second.prev)? {
+                    return Err(LogicError::InvalidMutation("Slash proof blocks are not at the same height".into()).into());
+                }
+
+                self.is_signed_by(state, &proof.first, id)?;
+                self.is_signed_by(state, &proof.second, id)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn description(&self) -> &'static str {
+        "Slashes must include unused proof that the validator signed two blocks at the same height."
+    }
+}
+
+
+/// Watches for new blocks and submits a slash txn whenever it sees a validator sign two competing
+/// blocks at the same height.
+pub struct DoubleSignDetector {
+    rk: Arc<RecordKeeper>,
+
+    /// Key used to sign the slash txns we generate
+    key: PKey,
+
+    /// How many blocks back to remember signatures for
+    memory: u64,
+
+    /// The first header each validator was seen signing at a given height
+    seen: BTreeMap<u64, HashMap<U160, BlockHeader>>
+}
+
+impl DoubleSignDetector {
+    pub fn new(rk: Arc<RecordKeeper>, key: PKey, memory: u64) -> DoubleSignDetector {
+        DoubleSignDetector { rk, key, memory, seen: BTreeMap::new() }
+    }
+
+    /// Register with the record keeper and process new blocks on the given reactor.
+    pub fn watch(mut self, handle: &Handle) {
+        let (tx, rx) = mpsc::channel(10);
+        self.rk.register_record_listener(tx);
+
+        handle.spawn(rx.for_each(move |e| {
+            if let RecordEvent::NewBlock { block, .. } = e {
+                match self.process(&block.header) {
+                    Ok(Some(txn)) => {
+                        info!("Validator double-signed at block {}, submitting slash txn {}", block.calculate_hash(), txn.calculate_hash());
+                        if let Err(e) = self.rk.add_pending_txn(txn, true) {
+                            warn!("Slash txn was rejected: {}", e);
+                        }
+                    },
+                    Ok(None) => (),
+                    Err(e) => warn!("Could not check block for double-signing: {}", e)
+                }
+            }
+
+            Ok(())
+        }));
+    }
+
+    /// Record the signers of a new block header and produce a slash txn if one of them has already
+    /// signed a different block at the same height.
+    pub fn process(&mut self, header: &BlockHeader) -> Result<Option<Txn>, Error> {
+        let block_data = match bincode::deserialize::<EPoSBlockData>(&header.blob[..]) {
+            Ok(d) => d,
+            Err(_) => return Ok(None) // not something we can judge, e.g. genesis
+        };
+
+        let height = self.rk.get_block_height(&header.calculate_hash())?;
+        if height + self.memory < self.rk.get_block_height(&self.rk.get_current_block_hash())? {
+            return Ok(None); // too old to be of interest
+        }
+
+        let hash = header.calculate_hash();
+        let mut mutation = Mutation::new();
+        {
+            let signers = self.seen.entry(height).or_insert_with(HashMap::new);
+            for &(ref der, _) in block_data.sigs.iter() {
+                let id = hash_pub_key(der);
+                if let Some(first) = signers.get(&id) {
+                    if first.calculate_hash() != hash {
+                        let amount = self.rk.get_validator_stake(&id)?;
+                        let proof = DoubleSignProof::new(first.clone(), header.clone()).encode();
+
+                        // another node may have beaten us to it
+                        if amount > 0 && !self.rk.is_slash_evidence_used(&id, &proof)? {
+                            mutation.changes.push(Change::Slash { id, amount, proof });
+                        }
+                    }
+                    continue;
+                }
+
+                signers.insert(id, header.clone());
+            }
+        }
+
+        // forget anything too old to still be competing
+        let cutoff = height.saturating_sub(self.memory);
+        self.seen = self.seen.split_off(&cutoff);
+
+        if mutation.changes.is_empty() {
+            return Ok(None);
+        }
+
+        let creator = hash_pub_key(&self.key.public_key_to_der().unwrap());
+        Ok(Some(Txn::new(creator, mutation).sign(&self.key)))
+    }
+}
+
+
+#[test]
+fn slash_double_signing() {
+    use record_keeper::test_record_keeper;
+    use record_keeper::database::BLOCK_REWARD;
+    use signer::generate_private_key;
+    use time::Time;
+
+    let key = generate_private_key();
+    let id = hash_pub_key(&key.public_key_to_der().unwrap());
+    let rk = test_record_keeper(&key, 1 << 20, 16, 3600);
+
+    // sign two competing blocks at the same height
+    let (target, _) = calculate_validator_target(&rk.get_current_block_hash(), 100, |h|
+        rk.get_block_header(h).map_err(|e| ForgeError(format!("{:?}", e)))
+    ).unwrap();
+    let mut first = rk.create_block().unwrap();
+    let mut second = first.clone();
+    second.timestamp = Time::from_milliseconds(first.timestamp.millis() + 1);
+    EPoSBlockData::apply_block(&mut first, 1, &target, &key).unwrap();
+    EPoSBlockData::apply_block(&mut second, 1, &target, &key).unwrap();
+
+    let proof = DoubleSignProof::new(first.header.clone(), second.header.clone()).encode();
+    let slash = |amount| {
+        let mut mutation = Mutation::new();
+        mutation.changes.push(Change::Slash { id, amount, proof: proof.clone() });
+        Txn::new(id, mutation).sign(&key)
+    };
+
+    assert!(rk.add_pending_txn(slash(BLOCK_REWARD), true).unwrap());
+    let block = rk.create_block().unwrap();
+    assert!(rk.add_block(&block, true).unwrap());
+    assert_eq!(rk.get_validator_stake(&id).unwrap(), 0);
+
+    // the same evidence cannot be used twice
+    match rk.add_pending_txn(slash(1), true) {
+        Err(Error::Logic(LogicError::Duplicate)) => (),
+        r => panic!("Expected the replayed slash to be rejected, got {:?}", r)
+    }
+}
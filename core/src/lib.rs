@@ -9,6 +9,8 @@ extern crate hyper;
 extern crate jsonrpc_core;
 extern crate jsonrpc_http_server;
 extern crate jsonrpc_macros;
+extern crate jsonrpc_pubsub;
+extern crate jsonrpc_ws_server;
 extern crate libc;
 extern crate ntp;
 extern crate openssl;
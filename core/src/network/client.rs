@@ -19,18 +19,20 @@ use tokio_core::net::{TcpListener, UdpSocket};
 // needed for "framed"
 use tokio_io::AsyncRead;
 
-use primitives::U256;
+use primitives::{U256, Txn};
+use forging::HeaderVerifier;
 use record_keeper::{RecordKeeper, RecordEvent};
 use signer::generate_private_key;
 use util::QuitSignal;
 
 use network::context::*;
-use network::node::{Node, NodeEndpoint, Protocol};
+use network::headers::ShardHeaderIndex;
+use network::node::{Node, NodeEndpoint, LocalNode, Protocol};
 use network::job::NetworkJob;
 //use network::ntp;
 use network::protocol::*;
 use network::session::SessionInfo;
-use network::shard::ShardMode;
+use network::shard::{ShardMode, ShardStatus};
 use network::tcp::TCPCodec;
 use network::udp::UDPCodec;
 
@@ -67,7 +69,11 @@ pub struct ClientConfig {
     pub bind_addr: SocketAddr,
 
     /// A private key used to sign and identify our own node data
-    pub private_key: PKey
+    pub private_key: PKey,
+
+    /// Where the header chains of `Auxillery` and `QueryOnly` shards are kept. Give the same index
+    /// to record keeper as `shard_headers` so it can check claims and child block references.
+    pub shard_headers: Arc<ShardHeaderIndex>
 }
 
 impl ClientConfig {
@@ -100,7 +106,8 @@ impl ClientConfig {
             max_nodes: 16,
             hostname: String::from(""),
             port: ClientConfig::DEFAULT_PORT,
-            bind_addr: SocketAddr::new("0.0.0.0".parse().unwrap(), ClientConfig::DEFAULT_PORT)
+            bind_addr: SocketAddr::new("0.0.0.0".parse().unwrap(), ClientConfig::DEFAULT_PORT),
+            shard_headers: Arc::new(ShardHeaderIndex::new())
         }
     }
 }
@@ -108,17 +115,40 @@ impl ClientConfig {
 pub enum ClientMsg {
     GetStatistics(oneshot::Sender<Statistics>),
     GetPeerInfo(oneshot::Sender<Vec<SessionInfo>>),
-    AddNode(U256, Node),
-    DropNode(U256, Node),
-
-    AttachNetwork(U256, ShardMode),
-    DetachNetwork(U256),
+    /// Try to connect to a node on a network. Replies with whether a session could be opened.
+    AddNode(U256, Node, oneshot::Sender<bool>),
+    /// Forget a node and disconnect from it. Replies false if the node was not known.
+    DropNode(U256, Node, oneshot::Sender<bool>),
+    /// Disconnect from the address of a node and refuse to connect to it again. Replies false if the
+    /// network is not attached or the address of the node could not be resolved.
+    BanNode(U256, Node, oneshot::Sender<bool>),
+    /// Get the nodes in the repository of a network, or None if it is not attached
+    GetKnownNodes(U256, oneshot::Sender<Option<Vec<LocalNode>>>),
+
+    /// Start participating in a network. Replies with the port assigned to it.
+    AttachNetwork(U256, ShardMode, oneshot::Sender<Result<u8, AttachError>>),
+    /// Stop participating in a network. Replies false if it was not attached.
+    DetachNetwork(U256, oneshot::Sender<bool>),
+    /// Get the status of every attached network
+    ListNetworks(oneshot::Sender<Vec<ShardStatus>>),
 
     ShouldForge(U256, oneshot::Sender<bool>),
 
     SendBroadcast(U256, u8, Vec<u8>),
 
-    RegisterBroadcastReceiver(u8, Arc<BroadcastReceiver + Send + Sync>)
+    RegisterBroadcastReceiver(u8, Arc<BroadcastReceiver + Send + Sync>),
+
+    /// Set how headers are checked on `QueryOnly` shards
+    RegisterHeaderVerifier(Arc<HeaderVerifier>),
+
+    /// Ask a peer for blocks or txns on a `QueryOnly` shard, which will then be available from the header chain
+    QueryData(U256, Vec<U256>),
+
+    /// Get the hash and height of the highest known header on a `QueryOnly` shard
+    GetHeaderHead(U256, oneshot::Sender<Option<(U256, u64)>>),
+
+    /// Get a txn which was fetched with `QueryData` on a `QueryOnly` shard
+    GetQueriedTxn(U256, U256, oneshot::Sender<Option<Txn>>)
 }
 
 /// Statistical information which can be queried from the network client
@@ -177,6 +207,12 @@ impl Client {
             if let Message::Introduce { node, network_id, .. } = p.payload.msg.clone() {
                 // new session?
                 if let Some(ref shard) = *self.context.get_shard_by_id(&network_id) {
+
+					// the endpoint in the introduction may not be where the packet came from
+					if shard.is_banned(&addr.ip()) {
+						debug!("Ignoring introduction from banned address {}", addr);
+						return Box::new(future::ok(()));
+					}
 					
 					let ctx = self.context.clone();
 					let node2 = node.clone();
@@ -342,39 +378,75 @@ impl Client {
                 let f: future::FutureResult<(), ()> = match p {
                     ClientMsg::GetStatistics(r) => future::result(r.send(this.get_stats()).map_err(|_| ())),
                     ClientMsg::GetPeerInfo(r) => future::result(r.send(this.get_peer_info()).map_err(|_| ())),
-                    ClientMsg::AddNode(network_id, node) => {
+                    ClientMsg::AddNode(network_id, node, r) => {
                         let p = this.context.resolve_port(&network_id);
                         if p < 255  {
+                            // reply once we know if the session could be opened
                             let f = this.context.get_shard(p).as_ref().unwrap()
                                 .open_session(node, None, true)
-                                .then(move |r| {
-									// TODO: Return an actual response
-									if let Err(_) = r {
-										warn!("Could not add node to connection list: is the hostname correct/resolvable?");
+                                .then(move |res| {
+									if let Err(ref e) = res {
+										warn!("Could not add node to connection list: {}", e);
 									}
 									
-									Ok(())
+									r.send(res.is_ok()).map_err(|_| ())
 								});
 							
 							this.context.event_loop.spawn(f);
+							future::ok(())
+                        }
+                        else {
+                            future::result(r.send(false).map_err(|_| ()))
                         }
+                    },
+                    ClientMsg::DropNode(network_id, node, r) => {
+                        let removed = match *this.context.get_shard_by_id(&network_id) {
+                            Some(ref shard) => shard.remove_node(&node.endpoint),
+                            None => false
+                        };
 
+                        future::result(r.send(removed).map_err(|_| ()))
+                    },
+                    ClientMsg::BanNode(network_id, node, r) => {
+                        // the ban is on the address the endpoint resolves to
+                        let ctx = Rc::clone(&this.context);
+                        let f = node.endpoint.as_socketaddr().then(move |res| {
+                            let banned = match (res, &*ctx.get_shard_by_id(&network_id)) {
+                                (Ok(addr), &Some(ref shard)) => { shard.ban_node(addr.ip()); true },
+                                (Err(e), _) => { warn!("Could not resolve node to ban: {}", e); false },
+                                _ => false
+                            };
+
+                            r.send(banned).map_err(|_| ())
+                        });
+
+                        this.context.event_loop.spawn(f);
                         future::ok(())
                     },
-                    ClientMsg::DropNode(network_id, _) => {
-                        let p = this.context.resolve_port(&network_id);
-                        if p < 255 {
-                            // not implemented
+                    ClientMsg::GetKnownNodes(network_id, r) => {
+                        let nodes = this.context.get_shard_by_id(&network_id).as_ref()
+                            .map(|shard| shard.get_known_nodes());
+
+                        future::result(r.send(nodes).map_err(|_| ()))
+                    },
+                    ClientMsg::AttachNetwork(network_id, mode, r) => {
+                        let port = NetworkContext::attach_network(&this.context, network_id, mode);
+                        if port.is_ok() {
+                            // the shard may be the parent of child shards we should help serve
+                            match this.context.rk.get_child_shards() {
+                                Ok(children) => NetworkContext::attach_child_shards(&this.context, &children),
+                                Err(e) => warn!("Could not look up child shards: {}", e)
+                            }
                         }
 
-                        future::ok(())
+                        future::result(r.send(port).map_err(|_| ()))
                     },
-                    ClientMsg::AttachNetwork(network_id, mode) => future::result(NetworkContext::attach_network(&this.context, network_id, mode).map(|_| ())),
-                    ClientMsg::DetachNetwork(network_id) => {
-                        this.context.detach_network(&network_id);
+                    ClientMsg::DetachNetwork(network_id, r) => {
+                        let detached = this.context.detach_network(&network_id);
 
-                        future::ok(())
+                        future::result(r.send(detached).map_err(|_| ()))
                     },
+                    ClientMsg::ListNetworks(r) => future::result(r.send(this.get_network_status()).map_err(|_| ())),
 
                     ClientMsg::ShouldForge(network_id, r) => {
                         // must be connected to at least one node on the selected network (possibly for a period of time)
@@ -419,6 +491,40 @@ impl Client {
                         this.register_broadcast_receiver(id, receiver);
                         
                         future::ok(())
+                    },
+
+                    ClientMsg::RegisterHeaderVerifier(verifier) => {
+                        this.context.set_header_verifier(verifier);
+
+                        future::ok(())
+                    },
+
+                    ClientMsg::QueryData(network_id, hashes) => {
+                        if let Some(ref shard) = *this.context.get_shard_by_id(&network_id) {
+                            if !shard.query_data(hashes) {
+                                debug!("No peers available to query data from");
+                            }
+                        }
+
+                        future::ok(())
+                    },
+
+                    ClientMsg::GetHeaderHead(network_id, r) => {
+                        let head = match *this.context.get_shard_by_id(&network_id) {
+                            Some(ref shard) => shard.get_header_chain().as_ref().map(|c| c.head()),
+                            None => None
+                        };
+
+                        future::result(r.send(head).map_err(|_| ()))
+                    },
+
+                    ClientMsg::GetQueriedTxn(network_id, hash, r) => {
+                        let txn = match *this.context.get_shard_by_id(&network_id) {
+                            Some(ref shard) => shard.get_header_chain().as_ref().and_then(|c| c.get_txn(&hash).cloned()),
+                            None => None
+                        };
+
+                        future::result(r.send(txn).map_err(|_| ()))
                     }
                 };
 
@@ -482,6 +588,8 @@ impl Client {
                         }
                         
                     },
+                    // new shards may have been split off which we should help serve
+                    RecordEvent::ChildShards {children} => NetworkContext::attach_child_shards(&this.context, &children),
                     _ => {}
                 }
 
@@ -542,6 +650,19 @@ impl Client {
         p
     }
 
+    pub fn get_network_status(&self) -> Vec<ShardStatus> {
+
+        let mut p = Vec::new();
+
+        for i in 0..255 {
+            if let Some(ref s) = *self.context.get_shard(i) {
+                p.push(s.get_status());
+            }
+        }
+
+        p
+    }
+
     pub fn get_stats(&self) -> Statistics {
 
         let mut stats = Statistics::new();
@@ -1,6 +1,7 @@
 use std::borrow::Borrow;
 use std::cell::*;
 use std::sync::Arc;
+use std::fmt;
 use std::io;
 use std::rc::Rc;
 use std::collections::HashMap;
@@ -12,8 +13,9 @@ use futures::sink::BoxSink;
 use tokio_core::reactor::*;
 
 use record_keeper::RecordKeeper;
+use forging::HeaderVerifier;
 
-use primitives::U256;
+use primitives::{U256, BoundingBox};
 use time::Time;
 use hash::hash_bytes;
 use env::get_client_name;
@@ -24,6 +26,27 @@ use network::node::{Node,NodeEndpoint,Protocol};
 use network::shard::*;
 use network::node::*;
 
+/// Reasons a network could not be attached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AttachError {
+    /// The network is already attached on the given port
+    AlreadyAttached(u8),
+    /// Too many networks are attached to take on another
+    TooManyShards,
+    /// A shard which follows headers needs its genesis header, which record keeper does not have
+    UnknownGenesis
+}
+
+impl fmt::Display for AttachError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AttachError::AlreadyAttached(port) => write!(f, "Network is already attached on port {}", port),
+            AttachError::TooManyShards => write!(f, "Too many networks are attached"),
+            AttachError::UnknownGenesis => write!(f, "The genesis block of the network is not known")
+        }
+    }
+}
+
 pub struct NetworkContext {
     /// Access to the backend database/management engine
     pub rk: Arc<RecordKeeper>,
@@ -49,6 +72,9 @@ pub struct NetworkContext {
     /// Receivers which are registered to receive events; any payloads not fitting to this list will be dropped.
     pub broadcast_receivers: [Cell<Option<Arc<BroadcastReceiver + Send + Sync>>>; 256],
 
+    /// Used to check headers on shards which only follow the header chain
+    header_verifier: RefCell<Option<Arc<HeaderVerifier>>>,
+
 
     /// Data structures associated with shard-specific information
     shards: [RefCell<Option<ShardInfo>>; 255],
@@ -85,6 +111,7 @@ impl NetworkContext {
             sink: Cell::new(None),
             received_broadcasts: RefCell::new(HashMap::new()),
             broadcast_receivers: init_array!(Cell<Option<Arc<BroadcastReceiver + Send + Sync>>>, 256, Cell::new(None)),
+            header_verifier: RefCell::new(None),
                 
             shards: init_array!(RefCell<Option<ShardInfo>>, 255, RefCell::new(None)),
             empty_shard: RefCell::new(None),
@@ -125,6 +152,14 @@ impl NetworkContext {
         }
     }
 
+    pub fn set_header_verifier(&self, verifier: Arc<HeaderVerifier>) {
+        *self.header_verifier.borrow_mut() = Some(verifier);
+    }
+
+    pub fn get_header_verifier(&self) -> Option<Arc<HeaderVerifier>> {
+        self.header_verifier.borrow().clone()
+    }
+
     /// Initialize a node repository from file given the ID
     /// NOTE: This is pretty slow, consider using sparingly
     fn load_node_repo(&self, network_id: U256) -> NodeRepository {
@@ -140,16 +175,30 @@ impl NetworkContext {
         repo
     }
 
-    /// Connect to the specified shard by shard ID. On success, returns the number of pending connections (the number of nodes)
-    /// A result value of 0 does not indicate failure; it simply means that we need some time to gain connections within the net.
-    /// Be patient.
-    pub fn attach_network(this: &Rc<NetworkContext>, network_id: U256, mode: ShardMode) -> Result<usize, ()> {
+    /// Connect to the specified shard by shard ID. On success, returns the port assigned to the shard.
+    /// Connections to nodes of the network are made in the background, so it may take some time
+    /// before the shard has any sessions.
+    pub fn attach_network(this: &Rc<NetworkContext>, network_id: U256, mode: ShardMode) -> Result<u8, AttachError> {
+
+        let existing = this.resolve_port(&network_id);
+        if existing < 255 {
+            return Err(AttachError::AlreadyAttached(existing));
+        }
 
         if this.num_shards.get() > 128 {
             // we risk overwhelming the ports
-            return Err(());
+            return Err(AttachError::TooManyShards);
         }
 
+        // auxillery and query only shards follow the header chain from genesis (which is the network
+        // id); the genesis of a child shard is kept by the parent which split it off
+        let genesis = match mode {
+            ShardMode::Auxillery | ShardMode::QueryOnly => Some(this.rk.get_block_header(&network_id)
+                .or_else(|_| this.rk.get_shard_genesis(&network_id))
+                .map_err(|_| AttachError::UnknownGenesis)?),
+            _ => None
+        };
+
         // first, setup the node repository
         let repo = this.load_node_repo(network_id);
         let node_count = repo.len();
@@ -168,20 +217,50 @@ impl NetworkContext {
         }
 
         // we can now get going
-        let si = ShardInfo::new(network_id, port, mode, Rc::clone(&this), repo);
+        let si = ShardInfo::new(network_id, port, mode, Rc::clone(&this), repo, genesis);
 
         let mut shard = this.shards[port as usize].borrow_mut();
         *shard = Some(si);
+        this.num_shards.set(this.num_shards.get() + 1);
 
         // TODO: Constant?
         if node_count >= 2 {
             // we can start connecting to nodes immediately
-            let r = shard.borrow().as_ref().unwrap().node_scan(8);
-            Ok(r)
+            let pending = shard.borrow().as_ref().unwrap().node_scan(8);
+            debug!("Connecting to {} nodes on port {}", pending, port);
         }
-        else {
-            // we need to resolve our way over to this shard (TODO)
-            Ok(0)
+        // otherwise we need to resolve our way over to this shard (TODO)
+
+        Ok(port)
+    }
+
+    /// Attach as `Auxillery` to any of the given child shards which have been split off from the
+    /// shard record keeper follows, as long as we are serving that shard ourselves. This should be
+    /// run when the list of child shards changes rather than for every block.
+    pub fn attach_child_shards(this: &Rc<NetworkContext>, children: &[(U256, BoundingBox)]) {
+        if children.iter().all(|&(ref id, _)| this.resolve_port(id) < 255) {
+            return; // nothing new to attach to
+        }
+
+        let parent = match this.rk.get_current_block_header() {
+            Ok(header) => if header.shard.is_zero() { header.calculate_hash() } else { header.shard },
+            Err(_) => return
+        };
+
+        match *this.get_shard_by_id(&parent) {
+            Some(ref shard) if !shard.is_light() => {},
+            _ => return
+        }
+
+        for &(id, _) in children {
+            if this.resolve_port(&id) < 255 {
+                continue; // already attached
+            }
+
+            debug!("Attaching to child shard: {}", id);
+            if NetworkContext::attach_network(this, id, ShardMode::Auxillery).is_err() {
+                warn!("Could not attach to child shard: {}", id);
+            }
         }
     }
 
@@ -199,6 +278,7 @@ impl NetworkContext {
 
         sh.as_ref().unwrap().close();
         *sh = None;
+        self.num_shards.set(self.num_shards.get() - 1);
 
         true
     }
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::error::Error as StdErr;
+use std::fmt;
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use forging::{HeaderVerifier, ForgeError};
+use primitives::{Block, BlockHeader, Txn, U256};
+use record_keeper::mutation::ShardHeaders;
+
+/// Reasons a header could not be added to a `HeaderChain`.
+#[derive(Debug)]
+pub enum HeaderError {
+    /// The parent of the header is not known yet
+    MissingPrevious,
+    /// The header verifier rejected the header
+    Invalid(ForgeError)
+}
+
+impl StdErr for HeaderError {
+    fn description(&self) -> &str {
+        match *self {
+            HeaderError::MissingPrevious => "The last block this references is not known to us.",
+            HeaderError::Invalid(ref e) => &e.0
+        }
+    }
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+/// The chain of block headers followed by a `ShardMode::Auxillery` or `ShardMode::QueryOnly`
+/// shard. Txns are not synced; instead they may be fetched on demand and are only kept if they can
+/// be shown to be part of a block in the chain.
+pub struct HeaderChain {
+    /// All known headers along with their height
+    headers: HashMap<U256, (BlockHeader, u64)>,
+
+    /// The hash and height of the highest known header
+    head: (U256, u64),
+
+    /// Txn lists of blocks which have been fetched and checked against their header
+    txn_lists: HashMap<U256, Vec<U256>>,
+
+    /// Txns which have been fetched and are listed by one of the known blocks
+    txns: HashMap<U256, Txn>
+}
+
+impl HeaderChain {
+    /// Start a new header chain from the genesis block of the shard.
+    pub fn new(genesis: BlockHeader) -> HeaderChain {
+        let hash = genesis.calculate_hash();
+        let mut headers = HashMap::new();
+        headers.insert(hash, (genesis, 1));
+
+        HeaderChain {
+            headers,
+            head: (hash, 1),
+            txn_lists: HashMap::new(),
+            txns: HashMap::new()
+        }
+    }
+
+    /// The hash and height of the highest known header.
+    pub fn head(&self) -> (U256, u64) {
+        self.head
+    }
+
+    pub fn get_header(&self, hash: &U256) -> Option<&BlockHeader> {
+        self.headers.get(hash).map(|h| &h.0)
+    }
+
+    pub fn get_height(&self, hash: &U256) -> Option<u64> {
+        self.headers.get(hash).map(|h| h.1)
+    }
+
+    /// Whether the header is on the chain leading up to the head and at least `depth` blocks below
+    /// it.
+    pub fn is_final(&self, header: &BlockHeader, depth: u64) -> bool {
+        let hash = header.calculate_hash();
+        let height = match self.get_height(&hash) {
+            Some(h) if h + depth <= self.head.1 => h,
+            _ => return false
+        };
+
+        // make sure it is not on a fork by walking down from the head
+        let mut cur = self.head.0;
+        loop {
+            match self.headers.get(&cur) {
+                Some(&(ref h, ht)) if ht > height => cur = h.prev,
+                Some(_) => return cur == hash,
+                None => return false
+            }
+        }
+    }
+
+    /// Add a header after checking it with the verifier, if one is given. Returns true if it was
+    /// added, false if it was already known, and `MissingPrevious` if its parent is unknown.
+    pub fn add_header(&mut self, header: BlockHeader, verifier: Option<&HeaderVerifier>) -> Result<bool, HeaderError> {
+        let hash = header.calculate_hash();
+        if self.headers.contains_key(&hash) {
+            return Ok(false);
+        }
+
+        let height = match self.get_height(&header.prev) {
+            Some(h) => h + 1,
+            None => return Err(HeaderError::MissingPrevious)
+        };
+
+        if let Some(verifier) = verifier {
+            let headers = &self.headers;
+            let get_header = |h: &U256| headers.get(h)
+                .map(|h| h.0.clone())
+                .ok_or_else(|| ForgeError(format!("Header ({}) is not known", h)));
+
+            verifier.verify_header(&header, height, &get_header).map_err(HeaderError::Invalid)?;
+        }
+
+        self.headers.insert(hash, (header, height));
+        if height > self.head.1 {
+            self.head = (hash, height);
+        }
+
+        Ok(true)
+    }
+
+    /// Remember the txn list of a fetched block if it matches a known header. Returns true if the
+    /// block was accepted.
+    pub fn add_block(&mut self, block: &Block) -> bool {
+        let hash = block.calculate_hash();
+        if !self.headers.contains_key(&hash) || Block::calculate_merkle_root(&block.txns) != block.merkle_root {
+            return false;
+        }
+
+        self.txn_lists.insert(hash, block.txns.clone());
+        true
+    }
+
+    /// Remember a fetched txn if it is listed by one of the fetched blocks. Returns true if the txn
+    /// was accepted.
+    pub fn add_txn(&mut self, txn: Txn) -> bool {
+        let hash = txn.calculate_hash();
+        if !self.txn_lists.values().any(|l| l.contains(&hash)) {
+            return false;
+        }
+
+        self.txns.insert(hash, txn);
+        true
+    }
+
+    pub fn get_txn(&self, hash: &U256) -> Option<&Txn> {
+        self.txns.get(hash)
+    }
+}
+
+
+/// The header chains of all the light shards this node follows. It is shared with record keeper,
+/// which checks cross-shard claims and child block references against it.
+pub struct ShardHeaderIndex {
+    chains: RwLock<HashMap<U256, Arc<RwLock<HeaderChain>>>>
+}
+
+impl ShardHeaderIndex {
+    pub fn new() -> ShardHeaderIndex {
+        ShardHeaderIndex { chains: RwLock::new(HashMap::new()) }
+    }
+
+    /// Get the header chain of a shard, starting a new one from its genesis if it is not followed
+    /// yet.
+    pub fn attach(&self, shard: U256, genesis: BlockHeader) -> Arc<RwLock<HeaderChain>> {
+        Arc::clone(self.chains.write().entry(shard)
+            .or_insert_with(|| Arc::new(RwLock::new(HeaderChain::new(genesis)))))
+    }
+
+    /// Stop following the header chain of a shard.
+    pub fn detach(&self, shard: &U256) {
+        self.chains.write().remove(shard);
+    }
+}
+
+impl ShardHeaders for ShardHeaderIndex {
+    fn is_final(&self, shard: &U256, header: &BlockHeader, depth: u64) -> bool {
+        self.chains.read().get(shard).map_or(false, |c| c.read().is_final(header, depth))
+    }
+
+    fn get_height(&self, shard: &U256, block: &U256) -> Option<u64> {
+        self.chains.read().get(shard).and_then(|c| c.read().get_height(block))
+    }
+
+    fn get_head(&self, shard: &U256) -> Option<(U256, u64)> {
+        self.chains.read().get(shard).map(|c| c.read().head())
+    }
+}
@@ -1,14 +1,17 @@
 use std::cell::Cell;
 use std::rc::Rc;
+use std::sync::Arc;
 
-use primitives::Block;
+use primitives::{Block, BlockHeader};
 
 use network::protocol::*;
 use network::context::NetworkContext;
 
 use futures::prelude::*;
 
-use primitives::U256;
+use primitives::{U256, U256_ZERO};
+
+use worker::WORKER;
 
 thread_local!(static ACTIVE_SYNCS: Cell<usize> = Cell::new(0));
 
@@ -29,6 +32,14 @@ pub enum NetworkJobData {
     /// The second constant indicates the predicted blocks downloaded (but not necessarily imported) so far
     SyncChain(Block, U256),
 
+    /// Synchronize the header chain of the given `QueryOnly` shard to the given header, or to the
+    /// head of the peer if there is none. The last constant indicates the last header imported so far
+    SyncHeaders(U256, Option<BlockHeader>, U256),
+
+    /// Fetch specific txns or blocks by hash which are needed to import the given block, and then
+    /// try to import it again.
+    FetchData(Block, Vec<U256>),
+
     /// Get a list of nodes from the remote peer for the given network id in order to grow our contacts
     FindNodes(U256)
 }
@@ -67,7 +78,12 @@ impl NetworkJob {
             &NetworkJobData::SyncChain(ref cblock, ref last) => Message::SyncBlocks {
                 last_block_hash: last.clone(),
                 target_block_hash: cblock.calculate_hash()
-            }
+            },
+            &NetworkJobData::SyncHeaders(_, ref target, ref last) => Message::SyncHeaders {
+                last_block_hash: last.clone(),
+                target_block_hash: target.as_ref().map_or(U256_ZERO, |t| t.calculate_hash())
+            },
+            &NetworkJobData::FetchData(_, ref hashes) => Message::QueryData(hashes.clone())
         }
     }
 
@@ -89,6 +105,44 @@ impl NetworkJob {
                 }
             },
 
+            NetworkJobData::SyncHeaders(network_id, target, _cur) => {
+                if let &NetworkJobData::SyncHeaders(ref onetwork_id, ref otarget, ref _ocur) = &other.data {
+                    if *network_id != *onetwork_id {
+                        return false;
+                    }
+
+                    // a sync to the head of the peer already covers any other target
+                    let (my_hash, otarget) = match (target.as_ref(), otarget) {
+                        (None, _) => return true,
+                        (Some(t), &Some(ref o)) => (t.calculate_hash(), o),
+                        (Some(_), &None) => return false
+                    };
+
+                    if my_hash == otarget.calculate_hash() {
+                        return true;
+                    }
+
+                    if my_hash == otarget.prev {
+                        *target = Some(otarget.clone());
+                        return true;
+                    }
+                }
+            },
+
+            NetworkJobData::FetchData(target, hashes) => {
+                if let &NetworkJobData::FetchData(ref otarget, ref ohashes) = &other.data {
+                    if target.calculate_hash() == otarget.calculate_hash() {
+                        for hash in ohashes {
+                            if !hashes.contains(hash) {
+                                hashes.push(*hash);
+                            }
+                        }
+
+                        return true;
+                    }
+                }
+            },
+
             _ => {}
         }
 
@@ -114,7 +168,9 @@ impl NetworkJob {
                 }
                 else if let &Message::DataError(ref err) = msg {
                     if let &DataRequestError::HashesNotFound(..) = err {
-                        // TODO: This seems like a good thing to act on
+                        // the peer does not know of the chain we are after, so the retry will go to
+                        // someone else
+                        debug!("Peer could not provide blocks for SyncChain job");
                     }
 
                     try = try + 1;
@@ -138,6 +194,119 @@ impl NetworkJob {
                 Some(nj)
             },
 
+            NetworkJobData::SyncHeaders(network_id, ref target, mut cur) => {
+
+                let mut try = self.try.get();
+
+                if let &Message::HeaderData(ref headers) = msg {
+                    if let Some(ref shard) = *ctx.get_shard_by_id(&network_id) {
+                        for header in headers {
+                            let hash = header.calculate_hash();
+                            if let Err(e) = shard.import_header(header.clone()) {
+                                warn!("Received invalid header ({}) during header sync: {}", hash, e);
+                                try = try + 1;
+                                break;
+                            }
+
+                            cur = hash;
+                        }
+
+                        let synced = match *target {
+                            Some(ref t) => shard.get_header_chain().as_ref().map_or(true, |c| c.get_header(&t.calculate_hash()).is_some()),
+                            // a partial response means we have reached the head of the peer
+                            None => headers.len() < HEADER_RESPONSE_SIZE
+                        };
+
+                        if synced {
+                            return None; // header chain is synced up
+                        }
+                    }
+                    else {
+                        return None; // shard is gone
+                    }
+
+                    if headers.is_empty() {
+                        try = try + 1;
+                    }
+                }
+                else if let &Message::DataError(..) = msg {
+                    try = try + 1;
+                }
+                else {
+                    // packet does not correspond to what we requested of the client
+                    warn!("Invalid response for SyncHeaders data: {:?}", msg);
+                    try = try + 1;
+                }
+
+                if try > MAX_JOB_RETRIES {
+                    warn!("Dropping job: {:?}", self);
+
+                    return None;
+                }
+
+                let nj = NetworkJob::new(NetworkJobData::SyncHeaders(network_id, target.clone(), cur));
+
+                nj.try.set(try);
+
+                Some(nj)
+            },
+
+            NetworkJobData::FetchData(ref target, ref hashes) => {
+
+                let mut try = self.try.get();
+
+                if let &Message::SpotChainData(ref blocks, ref txns) = msg {
+                    // import what we were given and then try the block again with the txns attached,
+                    // so they do not have to pass through the pending pool. If anything is still
+                    // missing (e.g. the peer only had some of it), a new job will be started.
+                    let rk = Arc::clone(&ctx.rk);
+                    let lcontext = Rc::clone(ctx);
+                    let blocks = blocks.clone();
+                    let txns = txns.clone();
+                    let block = target.clone();
+                    let d = target.clone();
+                    let network_id = target.shard;
+
+                    ctx.event_loop.spawn(WORKER.spawn_fn(move || {
+                        for b in blocks.iter() {
+                            if let Err(e) = rk.add_block(b, false) {
+                                debug!("Could not add fetched block ({}): {}", b.calculate_hash(), e);
+                            }
+                        }
+
+                        rk.add_block_with_txns(&d, txns, true)
+                    }).map(|_| ()).or_else(move |err| {
+                        handle_block_error(err, block, &lcontext, &network_id, try + 1);
+
+                        Ok::<(), ()>(())
+                    }));
+
+                    return None;
+                }
+                else if let &Message::DataError(..) = msg {
+                    // the peer does not have any of what we asked for, so the retry will go to
+                    // someone else
+                    try = try + 1;
+                }
+                else {
+                    // packet does not correspond to what we requested of the client
+                    warn!("Invalid response for FetchData data: {:?}", msg);
+                    try = try + 1;
+                }
+
+                if try > MAX_JOB_RETRIES {
+                    warn!("Dropping job: {:?}", self);
+
+                    return None;
+                }
+
+                let nj = NetworkJob::new(NetworkJobData::FetchData(target.clone(), hashes.clone()));
+
+                nj.try.set(try);
+
+                Some(nj)
+            },
+
             NetworkJobData::FindNodes(network_id) => {
 
                 if let &Message::NodeList { ref nodes, .. } = msg {
@@ -2,6 +2,7 @@ pub mod client;
 pub mod node;
 
 mod context;
+mod headers;
 mod job;
 
 mod protocol;
@@ -12,4 +13,6 @@ mod udp;
 mod ntp;
 mod shard;
 
-pub use self::shard::ShardMode;
\ No newline at end of file
+pub use self::context::AttachError;
+pub use self::headers::ShardHeaderIndex;
+pub use self::shard::{ShardMode, ShardStatus};
\ No newline at end of file
@@ -1,7 +1,7 @@
 use serde_json;
 use std::cmp::*;
 use std::fmt;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{Read, Write, Error, ErrorKind};
 use std::error::Error as BaseError;
@@ -207,6 +207,9 @@ impl PartialOrd for LocalNode {
 pub struct NodeRepository {
     available_nodes: HashMap<U160, LocalNode>,
     sorted_nodes: Vec<U160>,
+    /// Addresses which may not be added or connected to again. Bans are by address rather than by
+    /// the endpoint a node reports, since a node can claim any endpoint it likes.
+    banned: HashSet<IpAddr>,
     changes: usize
 }
 
@@ -219,6 +222,7 @@ impl NodeRepository {
         let mut nr = NodeRepository {
             available_nodes: HashMap::new(),
             sorted_nodes: Vec::new(),
+            banned: HashSet::new(),
             changes: 0
         };
 
@@ -257,6 +261,11 @@ impl NodeRepository {
 
     pub fn new_node(&mut self, node: Node) {
 
+        // nodes given by host name are checked once the name is resolved to connect to them
+        if node.endpoint.host.parse().ok().map_or(false, |ip| self.is_banned(&ip)) {
+            return;
+        }
+
         let hpk = node.get_hash_id();
         // sanity check
         if self.available_nodes.contains_key(&hpk) {
@@ -283,6 +292,48 @@ impl NodeRepository {
         }
     }
 
+    /// Remove any nodes which are reached at the given endpoint. Returns true if there were any.
+    pub fn remove_endpoint(&mut self, endpoint: &NodeEndpoint) -> bool {
+        let ids: Vec<U160> = self.available_nodes.iter()
+            .filter(|&(_, n)| n.node.endpoint == *endpoint)
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in ids.iter() {
+            self.remove(id);
+        }
+
+        !ids.is_empty()
+    }
+
+    /// Remove any nodes at the given address and prevent them from being added again.
+    pub fn ban(&mut self, ip: IpAddr) {
+        let ids: Vec<U160> = self.available_nodes.iter()
+            .filter(|&(_, n)| n.node.endpoint.host.parse::<IpAddr>().ok() == Some(ip))
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in ids.iter() {
+            self.remove(id);
+        }
+
+        if self.banned.insert(ip) {
+            self.changes += 1;
+        }
+    }
+
+    pub fn is_banned(&self, ip: &IpAddr) -> bool {
+        self.banned.contains(ip)
+    }
+
+    /// All of the known nodes, starting with the highest score.
+    pub fn list(&self) -> Vec<LocalNode> {
+        self.sorted_nodes.iter()
+            .filter_map(|id| self.available_nodes.get(id))
+            .cloned()
+            .collect()
+    }
+
     /// Increment the connection score for the given node ID. Does nothing if the node does not exist in the repo, so call after apply() if the node is new.
     /// Returns whether or not a change was made to the repo
     pub fn up_score(&mut self, node: &U160) -> bool {
@@ -363,6 +414,11 @@ impl NodeRepository {
         
         let serialized = serde_json::to_string_pretty(&saved).unwrap();
 
+        // bans are kept separately so the node list stays readable by older versions
+        let banned: Vec<&IpAddr> = self.banned.iter().collect();
+        File::create(&self.ban_store_path(name))?
+            .write_all(serde_json::to_string_pretty(&banned).unwrap().as_bytes())?;
+
         debug!("Save file to: {:?}", self.node_store_path(name).as_path());
 
         // open a file, put serialized data into it
@@ -380,6 +436,15 @@ impl NodeRepository {
     pub fn load(&mut self, name: &str) -> Result<u32, Error> {
         info!("Load stored nodes from file...");
 
+        if self.ban_store_path(name).as_path().is_file() {
+            let mut contents = String::new();
+            File::open(&self.ban_store_path(name).as_path())?.read_to_string(&mut contents)?;
+
+            let banned: Vec<IpAddr> = serde_json::from_str(&contents)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+            self.banned.extend(banned);
+        }
+
         // Handle file does not exist
         if !self.node_store_path(name).as_path().is_file() {
             // import seed nodes, and return 0 to indicate that the file was not there
@@ -408,6 +473,14 @@ impl NodeRepository {
         p
     }
 
+    fn ban_store_path(&self, name: &str) -> PathBuf {
+        let mut p = get_storage_dir().unwrap();
+        p.push("nodes");
+        p.push(name.to_owned() + ".banned.json");
+
+        p
+    }
+
     fn resort(&mut self) {
 
         let an = &self.available_nodes;
@@ -503,3 +576,33 @@ fn custom_node_vec() {
     assert_eq!(nr.get_nodes(3).name, "SuperTest Node 1");
     assert_eq!(nr.len(), 4);
 }
+
+#[test]
+fn banned_addresses() {
+    let mut nr = NodeRepository::new();
+
+    let node = |host: &str, key: u8| Node {
+        endpoint: NodeEndpoint {
+            protocol: Protocol::Udp,
+            host: String::from(host),
+            port: 42224
+        },
+        key: vec![key],
+        version: 1,
+        name: String::new()
+    };
+
+    nr.new_node(node("10.0.0.1", 1));
+    nr.new_node(node("10.0.0.2", 2));
+    assert_eq!(nr.len(), 2);
+
+    // every node at the address goes, whatever port or key it uses
+    let ip: IpAddr = "10.0.0.1".parse().unwrap();
+    nr.ban(ip);
+    assert!(nr.is_banned(&ip));
+    assert_eq!(nr.len(), 1);
+
+    nr.new_node(node("10.0.0.1", 3));
+    assert_eq!(nr.len(), 1);
+    assert_eq!(nr.get_nodes(0).get_hash_id(), hash_pub_key(&[2]));
+}
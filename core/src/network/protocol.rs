@@ -10,8 +10,10 @@ use record_keeper::{Error, LogicError, BlockchainEntry, Key, BlockPackage};
 use bin::Bin;
 use time::Time;
 use signer::*;
-use primitives::{U256,Block,Txn};
+use primitives::{U256,Block,BlockHeader,Txn};
 
+use network::context::NetworkContext;
+use network::headers::HeaderError;
 use network::job::{NetworkJob, NetworkJobData};
 use network::node::Node;
 use network::session::{Session,GenericSession};
@@ -37,6 +39,9 @@ pub const TIMEOUT_TOLERANCE: u64 = 3;
 /// The number of nodes which should be sent back on a list node request
 pub const NODE_RESPONSE_SIZE: usize = 8;
 
+/// The maximum number of headers which should be sent back on a header sync request
+pub const HEADER_RESPONSE_SIZE: usize = 64;
+
 
 //const NODE_SCAN_INTERVAL: u64 = 30000; // every 30 seconds
 pub const NODE_CHECK_INTERVAL: u64 = 5000; // every 5 seconds
@@ -172,7 +177,13 @@ pub enum Message {
     Bye(ByeReason),
 
     /// Sent when a previous call to QueryData or SyncBlocks is not able to be fulfilled, in whole or in part.
-    DataError(DataRequestError)
+    DataError(DataRequestError),
+
+    /// Request only the block headers from the given block hash to the target block hash, used by nodes following the header chain.
+    /// A target of zero requests the headers up to the current head of the node
+    SyncHeaders { last_block_hash: U256, target_block_hash: U256 },
+    /// Returned in response to SyncHeaders with headers sorted from the lowest height to the greatest height
+    HeaderData(Vec<BlockHeader>)
 }
 
 /// Sent when data is not able to returned for some reason
@@ -257,6 +268,11 @@ impl Packet {
                 Some(())
             },
 
+            Message::NewTransaction(..) if shard.is_light() => {
+                // we do not have the state needed to validate txns
+                Some(())
+            },
+
             Message::NewTransaction(ref txn) => {
                 let d = txn.clone();
                 let rk = Arc::clone(&sess.get_context().rk);
@@ -283,6 +299,21 @@ impl Packet {
                 Some(())
             },
 
+            Message::NewBlock(ref block) if shard.is_light() => {
+                match shard.import_header(block.header.clone()) {
+                    Ok(_) => {},
+                    Err(HeaderError::MissingPrevious) => {
+                        // a job to sync the missing headers has been started
+                    },
+                    Err(e) => {
+                        debug!("Rejected new block header ({}): {}", block.calculate_hash(), e);
+                        sess.mark_abuse();
+                    }
+                }
+
+                Some(())
+            },
+
             Message::NewBlock(ref block) => {
                 let d = block.clone();
                 let d2 = block.clone();
@@ -292,47 +323,7 @@ impl Packet {
                 sess.get_context().event_loop.spawn(WORKER.spawn_fn(move || {
                     rk.add_block(&d, true)
                 }).map(|_| ()).or_else(move |err| {
-                    // react for this node's records here if they are bad
-                    match err {
-                        Error::NotFound(Key::Blockchain(missing_obj)) => {
-                            match missing_obj {
-                                BlockchainEntry::BlockHeader(_hash) => {
-
-                                    // get the current chain head
-                                    let current_head = lcontext.rk.get_current_block_hash();
-
-                                    // new job to find this block
-                                    let job = NetworkJob::new(NetworkJobData::SyncChain(d2, current_head));
-
-                                    if let Some(ref shard) = *lcontext.get_shard_by_id(&network_id) {
-                                        shard.assign_job(job);
-                                    }
-
-                                    // TODO: set a new work target
-                                    /*if let Err(e) = lcontext.job_targets.unbounded_send(
-                                        // TODO: Move out reference to record keep,r which could stall network thread!
-                                        (NetworkJob::new(network_id, incoming_hash, lcontext.rk.get_current_block_hash(), None), Some(prev))
-                                    ) {
-                                        // should never happen
-                                        warn!("Could not buffer new network job: {}", e);
-                                    };*/
-                                },
-                                BlockchainEntry::Txn(_hash) => {
-                                    // TODO: request a single txn from some node so we can get patched up
-                                },
-                                BlockchainEntry::TxnList(_hash) => {
-                                    // should never happen
-                                    panic!("Database is missing an entire txn list! Should never happen.");
-                                }
-                            }
-                        },
-                        Error::Logic(_e) => {
-                            // TODO: Mark on node record and kick
-                        },
-                        _ => {
-                            // TODO: most likely some internal error occured, but where?
-                        }
-                    }
+                    handle_block_error(err, d2, &lcontext, &network_id, 0);
 
                     Ok::<(), ()>(())
                 }));
@@ -374,26 +365,37 @@ impl Packet {
                             sess.send_reply(Message::ChainData(to, d), seq, true);
                         }
                     }
-                    else {
-                        match r.unwrap_err() {
-                            Error::NotFound(Key::Blockchain(missing_obj)) => {
-                                let h = match missing_obj {
-                                    BlockchainEntry::BlockHeader(hash) => hash,
-                                    BlockchainEntry::Txn(hash) => hash,
-                                    BlockchainEntry::TxnList(hash) => hash
-                                };
-
-                                if let Some(sess) = wsess.upgrade() {
-                                    sess.send_reply(Message::DataError(DataRequestError::HashesNotFound(vec![h])), seq, true);
-                                }
-                            },
+                    else if let Some(sess) = wsess.upgrade() {
+                        sess.send_reply(Message::DataError(data_request_error(r.unwrap_err())), seq, true);
+                    }
 
-                            _ => {
-                                // no idea what happened
-                                if let Some(sess) = wsess.upgrade() {
-                                    sess.send_reply(Message::DataError(DataRequestError::InternalError), seq, true);
-                                }
-                            }
+                    Ok::<(), ()>(())
+                }));
+
+                Some(())
+            },
+
+            Message::SyncHeaders { ref last_block_hash, ref target_block_hash } => {
+                let lbh = last_block_hash.clone();
+                let tbh = target_block_hash.clone();
+                let rk = Arc::clone(&sess.get_context().rk);
+                let wsess = Rc::downgrade(sess);
+                let seq = self.seq;
+                sess.get_context().event_loop.spawn(QUEUED_WORKER.spawn_fn(move || {
+                    let tbh = if tbh.is_zero() { rk.get_current_block_hash() } else { tbh };
+                    let headers = rk.get_headers_between(&lbh, &tbh, HEADER_RESPONSE_SIZE)?;
+
+                    if headers.is_empty() {
+                        return Err(Error::Logic(LogicError::Duplicate));
+                    }
+
+                    Ok(headers)
+                })
+                .then(move |r| {
+                    if let Some(sess) = wsess.upgrade() {
+                        match r {
+                            Ok(headers) => sess.send_reply(Message::HeaderData(headers), seq, true),
+                            Err(e) => sess.send_reply(Message::DataError(data_request_error(e)), seq, true)
                         }
                     }
 
@@ -403,6 +405,11 @@ impl Packet {
                 Some(())
             },
 
+            Message::HeaderData(..) => {
+                // handled by the job which requested it
+                Some(())
+            },
+
             Message::QueryData(ref hashes) => {
                 let d = hashes.clone();
                 // get stuff form the db
@@ -447,8 +454,26 @@ impl Packet {
                 Some(())
             },
 
-            Message::SpotChainData(ref _blocks, ref _hashes) => {
-                // TODO: Fill someday
+            Message::SpotChainData(ref blocks, ref txns) if shard.is_light() => {
+                // keep whatever can be shown to be part of the header chain
+                shard.with_header_chain(|chain| {
+                    for block in blocks {
+                        if !chain.add_block(block) {
+                            debug!("Received block ({}) which is not part of the header chain", block.calculate_hash());
+                        }
+                    }
+                    for txn in txns {
+                        if !chain.add_txn(txn.clone()) {
+                            debug!("Received txn ({}) which is not part of a known block", txn.calculate_hash());
+                        }
+                    }
+                });
+
+                Some(())
+            },
+
+            Message::SpotChainData(..) => {
+                // handled by the job which requested it
                 Some(())
             },
 
@@ -538,9 +563,8 @@ impl Packet {
             Message::DataError(ref err) => {
                 // data could not be requested: does this have to do with our currently active job?
                 match *err {
-                    DataRequestError::HashesNotFound(ref _hashes) => {
-                        unimplemented!();
-                        
+                    DataRequestError::HashesNotFound(..) => {
+                        // handled by the job which requested it, if any
                     }
                     _ => {
                         // TODO:
@@ -562,3 +586,80 @@ impl Packet {
         }
     }
 }
+
+/// React to a block which could not be imported. If it failed because we are missing some of the
+/// data it depends on, a job is started to fetch that data and import the block again. `try` is the
+/// number of attempts which have already been made to fill in the block.
+pub fn handle_block_error(err: Error, block: Block, ctx: &Rc<NetworkContext>, network_id: &U256, try: usize) {
+    // react for this node's records here if they are bad
+    match err {
+        Error::NotFound(Key::Blockchain(missing_obj)) => {
+            if try > MAX_JOB_RETRIES {
+                warn!("Giving up on importing block: {}", block.calculate_hash());
+                return;
+            }
+
+            let job = match missing_obj {
+                BlockchainEntry::BlockHeader(_hash) => {
+                    // a missing parent usually means we are more than one block behind, so sync the
+                    // whole chain up to this block rather than fetching it one block at a time
+                    let current_head = ctx.rk.get_current_block_hash();
+                    NetworkJob::new(NetworkJobData::SyncChain(block, current_head))
+                },
+                BlockchainEntry::Txn(_hash) => {
+                    // ask for all of the txns we are missing at once, finding them off the network thread
+                    let rk = Arc::clone(&ctx.rk);
+                    let lcontext = Rc::clone(ctx);
+                    let network_id = *network_id;
+                    ctx.event_loop.spawn(WORKER.spawn_fn(move || {
+                        let missing = rk.get_missing_txns(&block.txns);
+                        Ok::<_, ()>((block, missing))
+                    }).map(move |(block, missing)| {
+                        let job = NetworkJob::new(NetworkJobData::FetchData(block, missing));
+                        job.try.set(try);
+
+                        if let Some(ref shard) = *lcontext.get_shard_by_id(&network_id) {
+                            shard.assign_job(job);
+                        }
+                    }));
+
+                    return;
+                },
+                BlockchainEntry::TxnList(_hash) => {
+                    // should never happen
+                    panic!("Database is missing an entire txn list! Should never happen.");
+                }
+            };
+
+            job.try.set(try);
+
+            if let Some(ref shard) = *ctx.get_shard_by_id(network_id) {
+                shard.assign_job(job);
+            }
+        },
+        Error::Logic(_e) => {
+            // TODO: Mark on node record and kick
+        },
+        _ => {
+            // TODO: most likely some internal error occured, but where?
+        }
+    }
+}
+
+/// Describe why a request for data could not be fulfilled.
+fn data_request_error(err: Error) -> DataRequestError {
+    match err {
+        Error::NotFound(Key::Blockchain(missing_obj)) => {
+            let h = match missing_obj {
+                BlockchainEntry::BlockHeader(hash) => hash,
+                BlockchainEntry::Txn(hash) => hash,
+                BlockchainEntry::TxnList(hash) => hash
+            };
+
+            DataRequestError::HashesNotFound(vec![h])
+        },
+
+        // no idea what happened
+        _ => DataRequestError::InternalError
+    }
+}
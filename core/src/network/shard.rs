@@ -6,9 +6,10 @@ use rand::Rng;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::collections::HashSet;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::ops::Deref;
 use std::rc::Rc;
+use std::sync::Arc;
 
 use futures::prelude::*;
 use futures::future;
@@ -17,24 +18,49 @@ use tokio_core::net::TcpStream;
 use tokio_io::AsyncRead;
 
 use network::context::*;
+use network::headers::{HeaderChain, HeaderError};
+use parking_lot::{RwLock, RwLockReadGuard};
 use network::job::*;
-use network::node::{Node, NodeRepository, Protocol};
+use network::node::{Node, NodeEndpoint, NodeRepository, LocalNode, Protocol};
 use network::protocol::{Message, ByeReason, Packet, MAX_JOB_RETRIES};
 use network::session::{GenericSession, Session, SessionInfo, NewSessionOptions};
 use network::tcp::TCPCodec;
-use primitives::{U256, U160_ZERO, U160};
+use primitives::{U256, U160_ZERO, U160, BlockHeader};
 
 /// Defines the kind of interaction this node will take with a particular shard
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ShardMode {
     /// Full participation, operating in block mining, full work processing, full authority
     Primary,
-    /// This is a long term connection and we still validate and sync on this shard, but less processing, primarily just validation
+    /// This is a long term connection and we still validate and sync on this shard, but less processing, primarily just validation.
+    /// Like `QueryOnly`, only the verified block headers are synced; the parent of a child shard uses them to check anchors of its blocks.
     Auxillery,
     /// Used when connecting to a shard to only get information from authoritative network sources. Good for when a player views a arbitrary shard
-    /// for gameplay. 
+    /// for gameplay. Only block headers are synced, and txns are fetched on demand.
     QueryOnly
 }
 
+/// Statistical information about an attached shard
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ShardStatus {
+    pub network_id: U256,
+    pub port: u8,
+    pub mode: ShardMode,
+
+    /// Number of sessions which are past introductions
+    pub sessions: usize,
+
+    /// Number of nodes in the node repository
+    pub known_nodes: usize,
+
+    /// Whether blocks are currently being synced. Chain syncs are not tracked per shard, so this
+    /// is true for every full shard while any sync is running.
+    pub syncing: bool,
+
+    /// Height of the header chain (light shards only)
+    pub header_height: Option<u64>
+}
+
 pub struct ShardInfo {
 
     /// The network context
@@ -62,11 +88,24 @@ pub struct ShardInfo {
     
     /// If no nodes are connected, the broadcast could be lost before it reaches another node.
     /// We store unsent broadcasts here so we can ensure they are eventually sent.
-    unsent_broadcasts: RefCell<Vec<Message>>
+    unsent_broadcasts: RefCell<Vec<Message>>,
+
+    /// The chain of headers we follow instead of syncing full blocks (`Auxillery` and `QueryOnly`
+    /// shards only). It lives in the `ShardHeaderIndex` of the client so record keeper can check
+    /// claims and child block references against it.
+    headers: Option<Arc<RwLock<HeaderChain>>>,
+
+    /// True once the header chain has been asked to sync up to the head of a peer
+    header_sync_started: Cell<bool>
 }
 
 impl ShardInfo {
-    pub fn new(network_id: U256, port: u8, mode: ShardMode, context: Rc<NetworkContext>, repo: NodeRepository) -> ShardInfo {
+    pub fn new(network_id: U256, port: u8, mode: ShardMode, context: Rc<NetworkContext>, repo: NodeRepository, genesis: Option<BlockHeader>) -> ShardInfo {
+        let headers = match mode {
+            ShardMode::Auxillery | ShardMode::QueryOnly => genesis.map(|g| context.config.shard_headers.attach(network_id, g)),
+            _ => None
+        };
+
         ShardInfo {
             context: context,
             network_id: network_id,
@@ -76,7 +115,9 @@ impl ShardInfo {
             peer_ids: RefCell::new(HashSet::new()),
             last_peer_idx: Cell::new(0),
             node_repo: RefCell::new(repo),
-            unsent_broadcasts: RefCell::new(Vec::new())
+            unsent_broadcasts: RefCell::new(Vec::new()),
+            headers: headers,
+            header_sync_started: Cell::new(false)
         }
     }
 
@@ -224,15 +265,15 @@ impl ShardInfo {
                 }
                 else {
                     pids.insert(sess.get_remote_node().get_hash_id());
-                }
 
-                // TODO: for now this is a little inefficient (requires a U160 hash for each client every 5 seconds), but it works
-                // add introduced nodes to the repo
-                if sess.is_introduced() && self.node_repo.borrow().get(&sess.get_remote_node().get_hash_id()).is_none() {
-                    debug!("Add node to DB: {:?}", sess.get_remote_node().get_hash_id());
-                    self.node_repo.borrow_mut().new_node(
-                        sess.get_remote_node().clone()
-                    );
+                    // TODO: for now this is a little inefficient (requires a U160 hash for each client every 5 seconds), but it works
+                    // add introduced nodes to the repo
+                    if sess.is_introduced() && self.node_repo.borrow().get(&sess.get_remote_node().get_hash_id()).is_none() {
+                        debug!("Add node to DB: {:?}", sess.get_remote_node().get_hash_id());
+                        self.node_repo.borrow_mut().new_node(
+                            sess.get_remote_node().clone()
+                        );
+                    }
                 }
             }
 
@@ -249,6 +290,13 @@ impl ShardInfo {
         for job in jobs {
             self.assign_job(job);
         }
+
+        // a newly attached header chain has to catch up before new blocks can connect to it
+        if self.is_light() && !self.header_sync_started.get() {
+            let head = self.headers.as_ref().unwrap().read().head().0;
+            let started = self.assign_job(NetworkJob::new(NetworkJobData::SyncHeaders(self.network_id, None, head)));
+            self.header_sync_started.set(started);
+        }
     }
 
     pub fn open_session(&self, peer: Node, strm: Option<BoxSink<Packet, io::Error>>, introduce: bool) -> Box<Future<Item=SocketAddr, Error=io::Error>> {
@@ -280,6 +328,11 @@ impl ShardInfo {
 			}
 			
 			let addr = r.unwrap();
+
+			// bans are checked against the address the endpoint resolves to
+			if ctx.get_shard(port).as_ref().map_or(false, |shard| shard.is_banned(&addr.ip())) {
+				return Err(io::Error::new(io::ErrorKind::PermissionDenied, "Node has been banned on this network_id"));
+			}
 			
 			let mut opts = NewSessionOptions {
 				context: Rc::clone(&ctx), 
@@ -381,6 +434,10 @@ impl ShardInfo {
     /// Call to set this shard to a state where all nodes are disconnected and data should stop being validated/tracked
     pub fn close(&self) {
         debug!("Close shard: {}", self.network_id);
+        if self.is_light() {
+            self.context.config.shard_headers.detach(&self.network_id);
+        }
+
         for sess in self.sessions.borrow_mut().values_mut() {
             sess.close();
         }
@@ -399,6 +456,7 @@ impl ShardInfo {
             Message::NodeList { .. } => {},
             Message::FindNodes {..} => {},
             Message::ChainData(ref to, ref data) => debug!("Received {} bytes of chain data to block {}", data.len(), to),
+            Message::HeaderData(ref headers) => debug!("Received {} headers", headers.len()),
             _ => debug!("{} ==> {:?}", addr, &p)
         };
 
@@ -488,6 +546,57 @@ impl ShardInfo {
         return &self.network_id;
     }
 
+    /// True if this shard only follows block headers rather than syncing full blocks.
+    pub fn is_light(&self) -> bool {
+        self.headers.is_some()
+    }
+
+    /// Access the header chain of a light shard.
+    pub fn get_header_chain(&self) -> Option<RwLockReadGuard<HeaderChain>> {
+        self.headers.as_ref().map(|chain| chain.read())
+    }
+
+    /// Modify the header chain of a light shard, if this is one.
+    pub fn with_header_chain<F: FnOnce(&mut HeaderChain)>(&self, f: F) {
+        if let Some(ref chain) = self.headers {
+            f(&mut *chain.write());
+        }
+    }
+
+    /// Verify and add a header to the header chain. If its parent is unknown, a job is started to
+    /// sync the headers leading up to it.
+    pub fn import_header(&self, header: BlockHeader) -> Result<bool, HeaderError> {
+        let res = {
+            let verifier = self.context.get_header_verifier();
+            match self.headers {
+                Some(ref chain) => chain.write().add_header(header.clone(), verifier.as_ref().map(|v| &**v)),
+                None => return Ok(false)
+            }
+        };
+
+        if let Err(HeaderError::MissingPrevious) = res {
+            let head = self.headers.as_ref().unwrap().read().head().0;
+            self.assign_job(NetworkJob::new(NetworkJobData::SyncHeaders(self.network_id, Some(header), head)));
+        }
+
+        res
+    }
+
+    /// Ask a random peer for blocks or txns by hash. The results arrive as `SpotChainData`.
+    pub fn query_data(&self, hashes: Vec<U256>) -> bool {
+        let s = self.sessions.borrow();
+        let mut rng = rand::thread_rng();
+        let mut pulls: Vec<&Rc<GenericSession>> = s.values()
+            .filter(|s| s.is_introduced() && s.is_done().is_none())
+            .collect();
+        rng.shuffle(&mut pulls);
+
+        match pulls.first() {
+            Some(sess) => { sess.send(Message::QueryData(hashes), false); true },
+            None => false
+        }
+    }
+
     pub fn get_session_count(&self) -> usize {
         // filter only sessions which are past introductions
         let mut count = 0;
@@ -514,6 +623,70 @@ impl ShardInfo {
         vec
     }
 
+    /// Returns the mode, connectivity and sync status of this shard
+    pub fn get_status(&self) -> ShardStatus {
+        let header_height = self.headers.as_ref().map(|chain| chain.read().head().1);
+
+        ShardStatus {
+            network_id: self.network_id,
+            port: self.port,
+            mode: self.mode,
+            sessions: self.get_session_count(),
+            known_nodes: self.node_repo.borrow().len(),
+            syncing: header_height.is_none() && NetworkJob::chain_sync_exists(),
+            header_height
+        }
+    }
+
+    /// Forget about the nodes at an endpoint and close any sessions with them. They may still be
+    /// found again through other peers. Returns true if the node was known or connected.
+    pub fn remove_node(&self, endpoint: &NodeEndpoint) -> bool {
+        let removed = self.node_repo.borrow_mut().remove_endpoint(endpoint);
+        self.save_node_repo();
+        self.close_sessions_with(endpoint) || removed
+    }
+
+    /// Forget about the nodes at an address, close any sessions with them and refuse to connect
+    /// with them again. The ban is saved along with the node repository.
+    pub fn ban_node(&self, ip: IpAddr) {
+        for (addr, sess) in self.sessions.borrow().iter() {
+            if addr.ip() == ip {
+                sess.close();
+            }
+        }
+
+        self.node_repo.borrow_mut().ban(ip);
+        self.save_node_repo();
+    }
+
+    /// Check if connections from or to an address are refused.
+    pub fn is_banned(&self, ip: &IpAddr) -> bool {
+        self.node_repo.borrow().is_banned(ip)
+    }
+
+    /// All of the nodes in the repository, starting with the highest score.
+    pub fn get_known_nodes(&self) -> Vec<LocalNode> {
+        self.node_repo.borrow().list()
+    }
+
+    fn close_sessions_with(&self, endpoint: &NodeEndpoint) -> bool {
+        let mut closed = false;
+        for sess in self.sessions.borrow().values() {
+            if sess.get_remote_node().endpoint == *endpoint {
+                sess.close();
+                closed = true;
+            }
+        }
+
+        closed
+    }
+
+    fn save_node_repo(&self) {
+        if let Err(e) = self.node_repo.borrow_mut().save(format!("{}", self.network_id).as_str()) {
+            warn!("Failed to save nodes to file: {:?}", e);
+        }
+    }
+
     pub fn get_nodes_from_repo(&self, skip: usize, count: usize) -> Vec<Node> {
 
         let nrepo = self.node_repo.borrow();
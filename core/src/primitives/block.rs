@@ -96,6 +96,10 @@ impl Ord for Block {
     }
 }
 
+/// One step of a merkle proof, the hash to merge with at that level of the tree and whether it
+/// goes on the left (`true`) or the right (`false`).
+pub type MerkleBranch = (U256, bool);
+
 impl Block {
     /// Calculate the merkle root of a set of transactions.
     pub fn calculate_merkle_root(txn_set: &Vec<U256>) -> U256 {
@@ -103,24 +107,58 @@ impl Block {
         // list of those, repeat until we end up with a single hash.
         
         let mut hashes: Vec<U256> = txn_set.iter().cloned().collect();
-        let mut len = hashes.len();
-
-        while len > 1 {
-            let mut h: Vec<U256> = Vec::new();
-            
-            for i in Range(0, len - 1, 2) {
-                h.push( merge_hashes(&hashes[i], &hashes[i+1]) );
-            } if (len % 2) == 1 { //if an odd number, we will have a tailing hash we need to include
-                h.push(hashes[len - 1])
-            }
-
-            hashes = h;
-            len = hashes.len();
+
+        while hashes.len() > 1 {
+            hashes = merkle_level(&hashes);
         }
 
-        if len == 1 { hashes[0] }
+        if hashes.len() == 1 { hashes[0] }
         else { U256_ZERO }
     }
+
+    /// Construct a proof that a txn is part of this block which can be checked against the
+    /// merkle root with `verify_merkle_proof`. Returns `None` if the txn is not in the block.
+    pub fn merkle_proof(&self, txn: &U256) -> Option<Vec<MerkleBranch>> {
+        let mut index = self.txns.iter().position(|h| h == txn)?;
+        let mut hashes = self.txns.clone();
+        let mut proof = Vec::new();
+
+        while hashes.len() > 1 {
+            if index % 2 == 1 {
+                proof.push((hashes[index - 1], true));
+            } else if index + 1 < hashes.len() {
+                proof.push((hashes[index + 1], false));
+            } // else it is a tailing hash which moves up on its own
+
+            hashes = merkle_level(&hashes);
+            index /= 2;
+        }
+
+        Some(proof)
+    }
+}
+
+/// Check that a merkle proof shows `txn` is part of the txn list with the given merkle root.
+pub fn verify_merkle_proof(merkle_root: &U256, txn: &U256, proof: &[MerkleBranch]) -> bool {
+    let root = proof.iter().fold(*txn, |hash, &(ref other, left)|
+        if left { merge_hashes(other, &hash) }
+        else { merge_hashes(&hash, other) }
+    );
+    root == *merkle_root
+}
+
+/// Calculate the next level up of a merkle tree by merging each pair of hashes.
+fn merkle_level(hashes: &[U256]) -> Vec<U256> {
+    let len = hashes.len();
+    let mut h: Vec<U256> = Vec::new();
+
+    for i in Range(0, len - 1, 2) {
+        h.push( merge_hashes(&hashes[i], &hashes[i+1]) );
+    } if (len % 2) == 1 { //if an odd number, we will have a tailing hash we need to include
+        h.push(hashes[len - 1])
+    }
+
+    h
 }
 
 
@@ -214,3 +252,31 @@ fn calculate_merkle_root() {
     let of5 = Block::calculate_merkle_root(&bts);
     assert_ne!(of5, of4);
 }
+
+#[test]
+fn merkle_proof() {
+    let mut block = Block {
+        header: BlockHeader {
+            version: 1,
+            timestamp: Time::from_milliseconds(0),
+            shard: U256_ZERO,
+            prev: U256_ZERO,
+            merkle_root: U256_ZERO,
+            blob: Bin::new()
+        },
+        txns: Vec::new()
+    };
+
+    for i in 1..8 {
+        block.txns.push(U256::from(i * 10));
+        let root = Block::calculate_merkle_root(&block.txns);
+
+        for txn in block.txns.iter() {
+            let proof = block.merkle_proof(txn).unwrap();
+            assert!(verify_merkle_proof(&root, txn, &proof));
+            assert!(!verify_merkle_proof(&root, &U256::from(1), &proof));
+        }
+    }
+
+    assert!(block.merkle_proof(&U256::from(1)).is_none());
+}
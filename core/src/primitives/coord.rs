@@ -151,14 +151,8 @@ impl BoundingBox {
 
     /// Checks if any of the area of this box is within the other box.
     pub fn overlaps(self, other: BoundingBox) -> bool {
-        self.contains(other.top_left()) ||
-        self.contains(other.top_right()) ||
-        self.contains(other.bottom_left()) ||
-        self.contains(other.bottom_right()) ||
-        other.contains(self.top_left()) ||
-        other.contains(self.top_right()) ||
-        other.contains(self.bottom_left()) ||
-        other.contains(self.bottom_right())
+        (self.0).0 <= (other.1).0 && (other.0).0 <= (self.1).0 &&
+        (self.0).1 <= (other.1).1 && (other.0).1 <= (self.1).1
     }
 
     /// Calculates the intersection of two boxes and returns the resulting box which is equal to the
@@ -285,6 +279,7 @@ fn overlaps() {
     assert!(c.overlaps(b));
     assert!(a.overlaps(BoundingBox(Coord(0, -2), Coord(5, 1)))); // top left and bottom right corner overlap
     assert!(a.overlaps(BoundingBox(Coord(-3, -1), Coord(4, 4)))); // second box is completely surrounding A
+    assert!(a.overlaps(BoundingBox(Coord(0, -1), Coord(1, 5)))); // boxes cross without any corners inside
 }
 
 #[test]
@@ -1,6 +1,7 @@
 use bin::*;
+use bincode;
 use record_keeper::{PlotEvent, JPlotEvent};
-use primitives::{U160, JU160};
+use primitives::{U160, JU160, U256, JU256, BlockHeader, JBlockHeader, BoundingBox, Txn, MerkleBranch, verify_merkle_proof};
 use std::ops::{Deref, DerefMut};
 use std::collections::HashMap;
 
@@ -14,7 +15,23 @@ pub enum Change {
     PlotEvent(PlotEvent),
     NewValidator { pub_key: Bin },
     Slash { id: U160, amount: u64, proof: Bin },
-    Transfer { from: U160, to: HashMap<U160, u64> }
+    Transfer { from: U160, to: HashMap<U160, u64> },
+    /// Lock shares on this shard so they can be claimed on `to_shard`, along with any plot events
+    /// which should be delivered there. The first half of a cross-shard transfer.
+    ShardTransfer { id: U256, from: U160, to_shard: U256, to: HashMap<U160, u64>, events: Vec<PlotEvent> },
+    /// Claim what was locked on `from_shard` by the `ShardTransfer` with the same id. The `proof` is
+    /// an encoded `ShardTransferProof`. The second half of a cross-shard transfer.
+    ShardClaim { id: U256, from_shard: U256, proof: Bin },
+    /// Split off part of the plot space of this shard into child shards, each covering the given
+    /// area and starting from the given genesis, which is an origin block (`shard` and `prev` are
+    /// zero). Must be made by the admin.
+    SplitShard { children: Vec<(BoundingBox, BlockHeader)> },
+    /// Grow the area covered by a child shard. Must be made by the admin.
+    ExpandShard { id: U256, bounds: BoundingBox },
+    /// Anchor the head of a child shard into this shard so the parent can tell which fork of the
+    /// child is canonical. The block must be a verified header at `height` on the child, and
+    /// references must always move forward.
+    ChildBlockRef { shard: U256, block: U256, height: u64 }
 }
 
 impl Change {
@@ -29,9 +46,30 @@ impl Change {
             &Change::PlotEvent(ref e) => e.calculate_size(),
             &Change::NewValidator{ref pub_key} => pub_key.len() + 1,
             &Change::Slash{ref proof, ..} => 28 + proof.len() + 1,
-            &Change::Transfer{ref to, ..} => 20 + to.len() * 28
+            &Change::Transfer{ref to, ..} => 20 + to.len() * 28,
+            &Change::ShardTransfer{ref to, ref events, ..} =>
+                84 + to.len() * 28 + events.iter().fold(0, |total, e| total + e.calculate_size()),
+            &Change::ShardClaim{ref proof, ..} => 64 + proof.len() + 1,
+            &Change::SplitShard{ref children} =>
+                8 + children.iter().fold(0, |total, &(_, ref h)| total + 16 + 106 + h.blob.len() + 1),
+            &Change::ExpandShard{..} => 32 + 16,
+            &Change::ChildBlockRef{..} => 32 + 32 + 8
         }
     }
+
+    /// The plot events a `ShardClaim` delivers, which are those carried by the transfer it proves.
+    /// They go through the same rules and codec as the event of a `PlotEvent` change. Any other
+    /// change, or a claim with a bad proof, delivers none.
+    pub fn claimed_events(&self) -> Vec<PlotEvent> {
+        if let &Change::ShardClaim{ref id, ref proof, ..} = self {
+            if let Ok(proof) = ShardTransferProof::decode(proof) {
+                if let Some(&Change::ShardTransfer{ref events, ..}) = proof.find_transfer(id) {
+                    return events.clone();
+                }
+            }
+        }
+        Vec::new()
+    }
 }
 
 
@@ -103,28 +141,59 @@ impl Mutation {
         self.iter().fold(0, |total, c| total + c.calculate_size())
     }
 
+    /// Ticks of all the plot events this changes, including those claimed from other shards.
+    fn game_event_ticks(&self) -> Vec<u64> {
+        let mut ticks = Vec::new();
+        for c in self.iter() {
+            if let &Change::PlotEvent(ref e) = c {
+                ticks.push(e.tick);
+            }
+            ticks.extend(c.claimed_events().iter().map(|e| e.tick));
+        }
+        ticks
+    }
+
     pub fn earliest_game_event(&self) -> u64 {
-        use std::cmp::min;
-        self.iter()
-            .filter_map(|c|
-                if let &Change::PlotEvent(ref e) = c {
-                    Some(e.tick)
-                } else { None }
-            ).fold(<u64>::max_value(), |earliest, current|
-                min(earliest, current)
-            )
+        self.game_event_ticks().into_iter().min().unwrap_or(<u64>::max_value())
     }
 
     pub fn latest_game_event(&self) -> u64 {
-        use std::cmp::max;
-        self.iter()
-            .filter_map(|c|
-                if let &Change::PlotEvent(ref e) = c {
-                    Some(e.tick)
-                } else { None }
-            ).fold(0u64, |latest, current|
-                max(latest, current)
-            )
+        self.game_event_ticks().into_iter().max().unwrap_or(0)
+    }
+}
+
+
+/// Evidence that a `Change::ShardTransfer` was accepted on its source shard. This is serialized into
+/// the `proof` of a `Change::ShardClaim`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ShardTransferProof {
+    /// Header of the source shard block which includes the txn
+    pub header: BlockHeader,
+    /// The txn containing the transfer
+    pub txn: Txn,
+    /// Merkle branch from the txn up to the merkle root of the header
+    pub branch: Vec<MerkleBranch>
+}
+
+impl ShardTransferProof {
+    pub fn decode(proof: &Bin) -> Result<ShardTransferProof, bincode::Error> {
+        bincode::deserialize(&proof[..])
+    }
+
+    pub fn encode(&self) -> Bin {
+        bincode::serialize(self, bincode::Infinite).unwrap().into()
+    }
+
+    /// Whether the txn is part of the block the header belongs to.
+    pub fn is_included(&self) -> bool {
+        verify_merkle_proof(&self.header.merkle_root, &self.txn.calculate_hash(), &self.branch)
+    }
+
+    /// Find the `ShardTransfer` with the given id in the txn.
+    pub fn find_transfer(&self, id: &U256) -> Option<&Change> {
+        self.txn.mutation.iter().find(|c|
+            if let &&Change::ShardTransfer{id: ref tid, ..} = c { tid == id } else { false }
+        )
     }
 }
 
@@ -138,7 +207,12 @@ pub enum JChange {
     PlotEvent(JPlotEvent),
     NewValidator { pub_key: JBin },
     Slash { id: JU160, amount: u64, proof: JBin },
-    Transfer { from: JU160, to: HashMap<JU160, u64> }
+    Transfer { from: JU160, to: HashMap<JU160, u64> },
+    ShardTransfer { id: JU256, from: JU160, to_shard: JU256, to: HashMap<JU160, u64>, events: Vec<JPlotEvent> },
+    ShardClaim { id: JU256, from_shard: JU256, proof: JBin },
+    SplitShard { children: Vec<(BoundingBox, JBlockHeader)> },
+    ExpandShard { id: JU256, bounds: BoundingBox },
+    ChildBlockRef { shard: JU256, block: JU256, height: u64 }
 }
 
 impl From<Change> for JChange {
@@ -149,7 +223,12 @@ impl From<Change> for JChange {
             Change::PlotEvent(e) => JChange::PlotEvent(e.into()),
             Change::NewValidator{pub_key} => JChange::NewValidator{pub_key: pub_key.into()},
             Change::Slash{id, amount, proof} => JChange::Slash{id: id.into(), amount, proof: proof.into()},
-            Change::Transfer {from, to} => JChange::Transfer{from: from.into(), to: to.into_iter().map(|(k, v)| (k.into(), v)).collect()}
+            Change::Transfer {from, to} => JChange::Transfer{from: from.into(), to: to.into_iter().map(|(k, v)| (k.into(), v)).collect()},
+            Change::ShardTransfer {id, from, to_shard, to, events} => JChange::ShardTransfer{id: id.into(), from: from.into(), to_shard: to_shard.into(), to: to.into_iter().map(|(k, v)| (k.into(), v)).collect(), events: events.into_iter().map(Into::into).collect()},
+            Change::ShardClaim {id, from_shard, proof} => JChange::ShardClaim{id: id.into(), from_shard: from_shard.into(), proof: proof.into()},
+            Change::SplitShard {children} => JChange::SplitShard{children: children.into_iter().map(|(b, h)| (b, h.into())).collect()},
+            Change::ExpandShard {id, bounds} => JChange::ExpandShard{id: id.into(), bounds},
+            Change::ChildBlockRef {shard, block, height} => JChange::ChildBlockRef{shard: shard.into(), block: block.into(), height}
         }
     }
 }
@@ -162,7 +241,12 @@ impl Into<Change> for JChange {
             JChange::PlotEvent(e) => Change::PlotEvent(e.into()),
             JChange::NewValidator{pub_key} => Change::NewValidator{pub_key: pub_key.into()},
             JChange::Slash{id, amount, proof} => Change::Slash{id: id.into(), amount, proof: proof.into()},
-            JChange::Transfer {from, to} => Change::Transfer{from: from.into(), to: to.into_iter().map(|(k, v)| (k.into(), v)).collect()}
+            JChange::Transfer {from, to} => Change::Transfer{from: from.into(), to: to.into_iter().map(|(k, v)| (k.into(), v)).collect()},
+            JChange::ShardTransfer {id, from, to_shard, to, events} => Change::ShardTransfer{id: id.into(), from: from.into(), to_shard: to_shard.into(), to: to.into_iter().map(|(k, v)| (k.into(), v)).collect(), events: events.into_iter().map(Into::into).collect()},
+            JChange::ShardClaim {id, from_shard, proof} => Change::ShardClaim{id: id.into(), from_shard: from_shard.into(), proof: proof.into()},
+            JChange::SplitShard {children} => Change::SplitShard{children: children.into_iter().map(|(b, h)| (b, h.into())).collect()},
+            JChange::ExpandShard {id, bounds} => Change::ExpandShard{id: id.into(), bounds},
+            JChange::ChildBlockRef {shard, block, height} => Change::ChildBlockRef{shard: shard.into(), block: block.into(), height}
         }
     }
 }
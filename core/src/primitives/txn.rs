@@ -2,7 +2,7 @@ use bin::*;
 use bincode;
 use hash::hash_obj;
 use openssl::pkey::PKey;
-use primitives::{Mutation, JMutation, U256, U160, JU160};
+use primitives::{Change, Mutation, JMutation, U256, U160, JU160};
 use signer::{sign_bytes, verify_bytes};
 use std::cmp::Ordering;
 use std::mem::size_of;
@@ -56,6 +56,14 @@ impl Txn {
         }
     }
 
+    /// Create a txn which anchors the head of a child shard into its parent shard. The creator
+    /// must be a validator.
+    pub fn new_child_block_ref(creator: U160, shard: U256, block: U256, height: u64) -> Txn {
+        let mut mutation = Mutation::new();
+        mutation.changes.push(Change::ChildBlockRef { shard, block, height });
+        Txn::new(creator, mutation)
+    }
+
     pub fn calculate_hash(&self) -> U256 {
         hash_obj(self)
     }
@@ -120,14 +128,6 @@ impl Txn {
     //     Self::new_txn(ADD_VALIDATOR_TXN, pkey, mutation)
     // }
 
-    // /// Update the reference to a child-shard's current block in the chain. This should only happen
-    // /// if the reference has not been stored before (aka avoid duplicate references).
-    // /// TODO: create needed mutations
-    // pub fn new_child_block_ref_txn(pkey: &PKey) -> Txn {
-    //     let mutation = Mutation::new();
-    //     Self::new_txn(CHILD_BLOCK_REF_TXN, pkey, mutation)
-    // }
-
     // /// Create a transaction which indicates a mutation to two different shards. This information
     // /// will be propagated up and down the shard tree.
     // /// TODO: create needed mutations
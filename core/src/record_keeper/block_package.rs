@@ -71,9 +71,7 @@ impl BlockPackage {
             let mut txn_indicies: Vec<u16> = Vec::new();
             let mut new_txns: Vec<Txn> = Vec::new();
 
-            // size of block header and the txns, add one to list of txns to account for
-            // a possible termination deliminer
-            size += bincode::serialize(&block.header, bincode::Infinite).unwrap().len() + (block.txns.len() + 1) * 2;
+            size += Self::header_size(&block);
 
             for txn in block.txns {
                 let index = *txns_by_hash.get(&txn).unwrap();
@@ -96,6 +94,13 @@ impl BlockPackage {
         Ok(package)
     }
 
+    /// Size in bytes of a block header and its txn list within a package, not including the txns
+    /// themselves. Add `Txn::calculate_size` for each txn to get the full size of the block.
+    pub fn header_size(block: &Block) -> usize {
+        // add one to list of txns to account for a possible termination deliminer
+        bincode::serialize(&block.header, bincode::Infinite).unwrap().len() + (block.txns.len() + 1) * 2
+    }
+
     /// Convert the `BlockPackage` into a compressed binary representation which can be easily
     /// transferred or archived.
     pub fn zip(&self) -> Result<Vec<u8>, Error> {
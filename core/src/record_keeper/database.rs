@@ -1,14 +1,14 @@
 use bin::{Bin, AsBin};
 use bincode::{serialize, deserialize, Infinite, Bounded};
-use hash::hash_pub_key;
-use primitives::{U256, U160, Mutation, Change, Block, BlockHeader, Txn, RawEvent, RawEvents};
+use hash::{hash_pub_key, hash_bytes};
+use primitives::{U256, U256_ZERO, U160, Mutation, Change, Block, BlockHeader, BoundingBox, Txn, RawEvent, RawEvents, ShardTransferProof};
 use primitives::event;
 use time::Time;
-use rocksdb::{DB, Options, IteratorMode, DBCompressionType, WriteBatch};
+use rocksdb::{DB, Options, IteratorMode, Direction, DBCompressionType, WriteBatch as RocksWriteBatch};
 use rocksdb::Error as RocksDBError;
 use std::collections::{HashMap, HashSet, BTreeMap};
 use std::path::PathBuf;
-use super::{PlotID, PlotEvent};
+use super::{PlotID, PlotEvent, RecordKeeperIndexingStrategy};
 use super::error::*;
 use super::key::*;
 use num_cpus;
@@ -28,12 +28,50 @@ pub struct HeadRef {
 
 impl Default for HeadRef {
     fn default() -> HeadRef {
-        use primitives::U256_ZERO;
         HeadRef{block: U256_ZERO, height: 0}
     }
 }
 
 
+/// A list of raw writes which are to be applied to a database all at once. A value of `None` means
+/// the key should be deleted. This allows building up changes without depending on how the
+/// database itself is stored.
+#[derive(Clone, Debug, Default)]
+pub struct WriteBatch(Vec<(Bin, Option<Bin>)>);
+
+impl WriteBatch {
+    pub fn put(&mut self, key: &[u8], value: &[u8]) {
+        self.0.push((key.to_vec(), Some(value.to_vec())));
+    }
+
+    pub fn delete(&mut self, key: &[u8]) {
+        self.0.push((key.to_vec(), None));
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Check if the batch writes to or deletes the given key.
+    pub fn touches(&self, key: &[u8]) -> bool {
+        self.0.iter().any(|&(ref k, _)| &k[..] == key)
+    }
+}
+
+impl IntoIterator for WriteBatch {
+    type Item = (Bin, Option<Bin>);
+    type IntoIter = ::std::vec::IntoIter<(Bin, Option<Bin>)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+
 
 /// A Blockchain Database abstraction.
 ///
@@ -46,6 +84,15 @@ pub trait Database: Send + Sync {
     /// Check if there are no entries in the database.
     fn is_empty(&self) -> bool;
 
+    /// Which indexes and data this database keeps.
+    fn get_index_strategy(&self) -> RecordKeeperIndexingStrategy;
+
+    /// Return an error if the current indexing strategy is not at least `required`.
+    fn _require_index(&self, required: RecordKeeperIndexingStrategy, name: &str) -> Result<(), Error> {
+        if self.get_index_strategy().includes(required) { Ok(()) }
+        else { Err(Error::IndexDisabled(format!("The {} index is not enabled by the indexing strategy.", name))) }
+    }
+
     /// Retrieve raw data from the database. Should have no uses outside this class.
     #[inline]
     fn _get(&self, key: Key) -> Result<Bin, Error>;
@@ -58,6 +105,10 @@ pub trait Database: Send + Sync {
     #[inline]
     fn _delete(&mut self, key: Key) -> Result<(), Error>;
 
+    /// Retrieve every raw key/value pair in the network-state keyspace. Should have no uses outside
+    /// record keeper, where snapshots use it to copy the network state.
+    fn _get_network_entries(&self) -> Result<Vec<(Bin, Bin)>, Error>;
+
     /// Write a series of changes to the database.
     ///
     /// *Warning*: Some type of database will not implement this function.
@@ -105,14 +156,69 @@ pub trait Database: Send + Sync {
         } Ok(())
     }
 
+    /// Replace all of a plot's event buckets before the one containing `tick` with empty ones. The
+    /// buckets themselves are kept so that reading forward through them still works. Stops at the
+    /// first empty bucket since those before it were emptied by an earlier compaction.
+    fn _compact_event_buckets(&mut self, plot_id: PlotID, tick: u64) -> Result<(), Error> {
+        let empty_events = RawEvents::new();
+        let mut tick = (tick / PLOT_EVENT_BUCKET_SIZE) * PLOT_EVENT_BUCKET_SIZE;
+
+        while tick >= PLOT_EVENT_BUCKET_SIZE {
+            tick -= PLOT_EVENT_BUCKET_SIZE;
+            match self._get_plot_event_bucket(plot_id, tick)? {
+                Some(ref events) if !events.is_empty() =>
+                    self._put_plot_event_bucket(plot_id, tick, &empty_events)?,
+                _ => break // anything before it was already compacted
+            }
+        } Ok(())
+    }
+
+    /// Remove the contra of a block which can no longer be reorged and compact the event buckets
+    /// older than the events it added. Under the `Light` strategy, the txn bodies are removed as
+    /// well since they are only needed to walk the chain. Returns false if there was nothing to
+    /// prune, e.g. if it was already pruned.
+    fn prune_final_block(&mut self, hash: U256) -> Result<bool, Error> {
+        let contra = match self._get_contra(hash) {
+            Ok(c) => c,
+            Err(Error::NotFound(..)) => return Ok(false),
+            Err(e) => return Err(e)
+        };
+
+        if !self.get_index_strategy().includes(RecordKeeperIndexingStrategy::Standard) {
+            for txn in self.get_block(&hash)?.txns {
+                self._delete(BlockchainEntry::Txn(txn).into())?;
+            }
+        }
+
+        for change in contra.changes.iter() {
+            let mut events = change.claimed_events();
+            if let &Change::PlotEvent(ref e) = change {
+                events.push(e.clone());
+            }
+
+            for e in events {
+                self._compact_event_buckets(e.from, e.tick)?;
+                for id in e.to.iter() {
+                    self._compact_event_buckets(*id, e.tick)?;
+                }
+            }
+        }
+
+        self._delete(CacheEntry::ContraMut(hash).into())?;
+        Ok(true)
+    }
+
     /// Add a new transaction to the database.
     #[inline]
     fn add_txn(&mut self, txn: &Txn, receive_time: Time) -> Result<(), Error> {
         let hash = txn.calculate_hash();
         debug!("Adding txn ({}) to database", &hash);
         self._put(BlockchainEntry::Txn(hash).into(), &serialize(txn, Infinite).unwrap())?;
-        self._add_receive_time(hash, receive_time)?;
-        self._add_txn_to_account(&txn.creator, hash)
+
+        if self.get_index_strategy().includes(RecordKeeperIndexingStrategy::Full) {
+            self._add_receive_time(hash, receive_time)?;
+            self._add_txn_to_account(&txn.creator, hash)?;
+        } Ok(())
     }
 
     /// Retrieve a block header form the database given a hash.
@@ -139,8 +245,10 @@ pub trait Database: Send + Sync {
         // cache the block
         self._add_block_to_height(height, &hash)?;
         self._add_height_for_block(height, hash)?;
-        for txn in block.txns.iter() {
-            self._add_block_for_txn(*txn, hash)?;
+        if self.get_index_strategy().includes(RecordKeeperIndexingStrategy::Standard) {
+            for txn in block.txns.iter() {
+                self._add_block_for_txn(*txn, hash)?;
+            }
         }
 
         Ok(true)
@@ -194,6 +302,7 @@ pub trait Database: Send + Sync {
 
     /// Get the block(s) a txn is part of.
     fn get_txn_blocks(&self, hash: U256) -> Result<HashSet<U256>, Error> {
+        self._require_index(RecordKeeperIndexingStrategy::Standard, "txn blocks")?;
         let blocks: HashSet<U256> = deserialize(&self._get(CacheEntry::BlocksByTxn(hash).into())?)?;
         Ok(blocks)
 
@@ -218,6 +327,7 @@ pub trait Database: Send + Sync {
 
     /// Get the txns created by a given account.
     fn get_account_txns(&self, hash: &U160) -> Result<HashSet<U256>, Error> {
+        self._require_index(RecordKeeperIndexingStrategy::Full, "account txns")?;
         let res = self._get(CacheEntry::TxnsByAccount(*hash).into());
         let res = match res {
             Err(Error::NotFound{..}) => return Ok(HashSet::new()),
@@ -226,8 +336,43 @@ pub trait Database: Send + Sync {
         Ok(deserialize(&res?)?)
     }
 
+    /// Get up to `limit` txns created by an account which are part of the current chain, newest
+    /// first. Each txn comes with the height of its block and its index within that block, and the
+    /// txns listed are those before the `(height, index)` position given, if any. A position above
+    /// the current head lists nothing.
+    fn get_account_txns_before(&self, account: &U160, before: Option<(u64, usize)>, limit: usize) -> Result<Vec<(u64, usize, U256)>, Error> {
+        let current_height = self.get_current_block_height();
+        if before.map_or(false, |(h, _)| h > current_height) {
+            return Ok(Vec::new());
+        }
+
+        // find where each txn was accepted on the current chain, if anywhere
+        let mut page = Vec::new();
+        for txn in self.get_account_txns(account)? {
+            for block in map_not_found(self.get_txn_blocks(txn), HashSet::new())? {
+                let height = self.get_block_height(block)?;
+                if height > current_height || self.get_current_block_of_height(height)? != block {
+                    continue;
+                }
+
+                let index = self.get_block(&block)?.txns.iter().position(|t| *t == txn);
+                if let Some(index) = index {
+                    if before.map_or(true, |b| (height, index) < b) {
+                        page.push((height, index, txn));
+                    }
+                }
+                break;
+            }
+        }
+
+        page.sort_by(|a, b| (b.0, b.1).cmp(&(a.0, a.1)));
+        page.truncate(limit);
+        Ok(page)
+    }
+
     /// Get the time a txn was originally received.
     fn get_txn_receive_time(&self, txn: U256) -> Result<Time, Error> {
+        self._require_index(RecordKeeperIndexingStrategy::Full, "txn receive time")?;
         Ok(deserialize(&self._get(CacheEntry::TxnReceiveTime(txn).into())?)?)
     }
 
@@ -244,6 +389,41 @@ pub trait Database: Send + Sync {
         Ok(deserialize(&self._get(NetworkEntry::ValidatorStake(id).into())?)?)
     }
 
+    /// Get the child shards which have been split off from this shard along with the area each
+    /// covers.
+    fn get_child_shards(&self) -> Result<Vec<(U256, BoundingBox)>, Error> {
+        match self._get(NetworkEntry::ChildShards.into()) {
+            Ok(raw) => Ok(deserialize(&raw)?),
+            Err(Error::NotFound(..)) => Ok(Vec::new()),
+            Err(e) => Err(e)
+        }
+    }
+
+    /// Get the genesis header of a child shard which was split off from this shard.
+    #[inline]
+    fn get_shard_genesis(&self, id: &U256) -> Result<BlockHeader, Error> {
+        Ok(deserialize(&self._get(NetworkEntry::ShardGenesis(*id).into())?)?)
+    }
+
+    /// Get the latest block of a child shard which has been anchored into this shard, along with
+    /// its height on the child shard.
+    fn get_child_block_ref(&self, shard: &U256) -> Result<Option<(U256, u64)>, Error> {
+        match self._get(NetworkEntry::ChildBlockRef(*shard).into()) {
+            Ok(raw) => Ok(Some(deserialize(&raw)?)),
+            Err(Error::NotFound(..)) => Ok(None),
+            Err(e) => Err(e)
+        }
+    }
+
+    /// Check if a proof has already been used to slash a validator, so it may not be used again.
+    fn is_slash_evidence_used(&self, id: &U160, proof: &Bin) -> Result<bool, Error> {
+        match self._get(NetworkEntry::SlashEvidence(*id, hash_bytes(proof)).into()) {
+            Ok(_) => Ok(true),
+            Err(Error::NotFound(..)) => Ok(false),
+            Err(e) => Err(e)
+        }
+    }
+
     /// Return a list of **known** blocks which have a given height. If the block has not been added
     /// to the database, then it will not be included.
     fn get_blocks_of_height(&self, height: u64) -> Result<Vec<U256>, Error> {
@@ -291,6 +471,21 @@ pub trait Database: Send + Sync {
         Ok(headers)
     }
 
+    /// Get the block headers of the current chain from `from_height` to `to_height` inclusive, in
+    /// order of height. Heights beyond the current head are left out.
+    fn get_blocks_range(&self, from_height: u64, to_height: u64) -> Result<Vec<BlockHeader>, Error> {
+        let from_height = from_height.max(1); // genesis is height 1
+        let to_height = to_height.min(self.get_current_block_height());
+        if to_height < from_height {
+            return Ok(Vec::new());
+        }
+
+        self.iter_up(from_height)
+            .take((to_height - from_height + 1) as usize)
+            .map(|r| r.map(|(_, header)| header))
+            .collect()
+    }
+
     /// Get blocks before the `target` hash until it collides with the main chain. If the
     /// `last_known` hash lies between the target and the main chain, it will return the blocks
     /// between them, otherwise it will return the blocks from the main chain until target in that
@@ -568,23 +763,25 @@ pub trait Database: Send + Sync {
         } Ok(prior)
     }
 
-    /// Change a validator's stake by the amount indicated.
+    /// Change a validator's stake by the amount indicated. It is an error for the stake to go
+    /// negative or to overflow; rules should prevent this from ever being attempted.
     fn _change_validator_stake(&mut self, id: U160, amount: i64) -> Result<(), Error> {
         let db_key: Key = NetworkEntry::ValidatorStake(id).into();
-        let value: u64 = match self._get(db_key.clone()) {
-            Ok(v) => deserialize(&v)?,
-            Err(Error::NotFound(..)) => 0,
-            Err(e) => return Err(e)
-        };
+        let value: u64 = map_not_found(self.get_validator_stake(id), 0)?;
 
         let value = if amount >= 0 {
-            value + amount as u64
+            value.checked_add(amount as u64)
         } else {
-            value - (-amount) as u64
-        };
+            value.checked_sub((-amount) as u64)
+        }.ok_or(LogicError::NotEnoughShares)?;
         self._put(db_key, &serialize(&value, Bounded(8)).unwrap())
     }
 
+    /// Replace the list of child shards.
+    fn _put_child_shards(&mut self, children: &Vec<(U256, BoundingBox)>) -> Result<(), Error> {
+        self._put(NetworkEntry::ChildShards.into(), &serialize(children, Infinite).unwrap())
+    }
+
     /// Add a new event to the specified plot.
     fn _add_event(&mut self, plot_id: PlotID, tick: u64, event: &RawEvent) -> Result<(), Error> {
         let event_list = self._get_plot_event_bucket(plot_id, tick)?;
@@ -660,9 +857,13 @@ pub trait Database: Send + Sync {
                 self._put(key, pub_key)?;
                 Change::NewValidator{pub_key: pub_key.clone()}
             },
-            &Change::Slash{id, amount, ..} => {
+            &Change::Slash{id, amount, ref proof} => {
+                // cannot take more than they have, so record what was actually taken for the contra
+                let stake = map_not_found(self.get_validator_stake(id), 0)?;
+                let amount = amount.min(stake);
                 self._change_validator_stake(id, -(amount as i64))?;
-                Change::Slash{id, amount, proof: Bin::new()}
+                self._put(NetworkEntry::SlashEvidence(id, hash_bytes(proof)).into(), &[])?;
+                Change::Slash{id, amount, proof: proof.clone()}
             },
             &Change::Transfer{from, ref to} => {
                 let mut sum = 0i64;
@@ -672,6 +873,55 @@ pub trait Database: Send + Sync {
                 }
                 self._change_validator_stake(from, -sum)?;
                 Change::Transfer{from, to: to.clone()}
+            },
+            &Change::ShardTransfer{id, from, ref to, ..} => {
+                // the shares are gone from this shard, but remember how much was locked
+                let sum = to.values().fold(0i64, |acc, &amount| acc + amount as i64);
+                self._change_validator_stake(from, -sum)?;
+                self._put(NetworkEntry::ShardLock(id).into(), &serialize(&sum, Bounded(8)).unwrap())?;
+                change.clone()
+            },
+            &Change::ShardClaim{id, from_shard, ref proof} => {
+                let transfer = ShardTransferProof::decode(proof)?;
+                if let Some(&Change::ShardTransfer{ref to, ref events, ..}) = transfer.find_transfer(&id) {
+                    for (&recipient, &amount) in to.iter() {
+                        self._change_validator_stake(recipient, amount as i64)?;
+                    }
+                    // delivered just as if they were `PlotEvent` changes
+                    for e in events.iter() {
+                        self._add_events(e)?;
+                    }
+                } else {
+                    return Err(LogicError::InvalidMutation("Claim proof does not contain the transfer".into()).into());
+                }
+                self._put(NetworkEntry::ShardClaim(from_shard, id).into(), &[])?;
+                change.clone()
+            },
+            &Change::SplitShard{ref children} => {
+                let mut known = self.get_child_shards()?;
+                for &(bounds, ref genesis) in children.iter() {
+                    let id = genesis.calculate_hash();
+                    self._put(NetworkEntry::ShardGenesis(id).into(), &serialize(genesis, Infinite).unwrap())?;
+                    known.push((id, bounds));
+                }
+                self._put_child_shards(&known)?;
+                change.clone()
+            },
+            &Change::ExpandShard{id, bounds} => {
+                let mut known = self.get_child_shards()?;
+                let prior = {
+                    let child = known.iter_mut().find(|c| c.0 == id)
+                        .ok_or_else(|| LogicError::InvalidMutation("Cannot expand an unknown shard".into()))?;
+                    ::std::mem::replace(&mut child.1, bounds)
+                };
+                self._put_child_shards(&known)?;
+                Change::ExpandShard{id, bounds: prior}
+            },
+            &Change::ChildBlockRef{shard, block, height} => {
+                // a height of zero in the contra means there was no prior reference
+                let (prior_block, prior_height) = self.get_child_block_ref(&shard)?.unwrap_or((U256_ZERO, 0));
+                self._put(NetworkEntry::ChildBlockRef(shard).into(), &serialize(&(block, height), Bounded(40)).unwrap())?;
+                Change::ChildBlockRef{shard, block: prior_block, height: prior_height}
             }
         })}
 
@@ -694,7 +944,10 @@ pub trait Database: Send + Sync {
                 let key: Key = NetworkEntry::ValidatorKey(id).into();
                 self._set_value(key.as_bin(), None)?;
             },
-            Change::Slash{id, amount, ..} => { self._change_validator_stake(id, amount as i64)?; },
+            Change::Slash{id, amount, proof} => {
+                self._change_validator_stake(id, amount as i64)?;
+                self._delete(NetworkEntry::SlashEvidence(id, hash_bytes(&proof)).into())?;
+            },
             Change::Transfer{from, to} => {
                 let mut sum = 0i64;
                 for (recipient, amount) in to {
@@ -702,6 +955,46 @@ pub trait Database: Send + Sync {
                     sum += amount as i64;
                 }
                 self._change_validator_stake(from, sum)?;
+            },
+            Change::ShardTransfer{id, from, to, ..} => {
+                let sum = to.values().fold(0i64, |acc, &amount| acc + amount as i64);
+                self._change_validator_stake(from, sum)?;
+                self._delete(NetworkEntry::ShardLock(id).into())?;
+            },
+            Change::ShardClaim{id, from_shard, proof} => {
+                let transfer = ShardTransferProof::decode(&proof)?;
+                if let Some(&Change::ShardTransfer{ref to, ref events, ..}) = transfer.find_transfer(&id) {
+                    for (&recipient, &amount) in to.iter() {
+                        self._change_validator_stake(recipient, -(amount as i64))?;
+                    }
+                    for e in events.iter() {
+                        self._remove_events(e)?;
+                    }
+                }
+                self._delete(NetworkEntry::ShardClaim(from_shard, id).into())?;
+            },
+            Change::SplitShard{children} => {
+                let ids: Vec<U256> = children.iter().map(|&(_, ref genesis)| genesis.calculate_hash()).collect();
+                for id in ids.iter() {
+                    self._delete(NetworkEntry::ShardGenesis(*id).into())?;
+                }
+                let mut known = self.get_child_shards()?;
+                known.retain(|c| !ids.contains(&c.0));
+                self._put_child_shards(&known)?;
+            },
+            Change::ExpandShard{id, bounds} => {
+                let mut known = self.get_child_shards()?;
+                if let Some(child) = known.iter_mut().find(|c| c.0 == id) {
+                    child.1 = bounds;
+                }
+                self._put_child_shards(&known)?;
+            },
+            Change::ChildBlockRef{shard, block, height} => {
+                if height == 0 {
+                    self._delete(NetworkEntry::ChildBlockRef(shard).into())?;
+                } else {
+                    self._put(NetworkEntry::ChildBlockRef(shard).into(), &serialize(&(block, height), Bounded(40)).unwrap())?;
+                }
             }
         }}
 
@@ -821,7 +1114,7 @@ pub trait Database: Send + Sync {
     fn _add_txn_to_account(&mut self, account: &U160, txn: U256) -> Result<(), Error> {
         let mut txns: HashSet<U256> = map_not_found(self.get_account_txns(account), HashSet::new())?;
         if txns.insert(txn) {
-            self._put(CacheEntry::BlocksByTxn(txn).into(), &serialize(&txns, Infinite).unwrap())
+            self._put(CacheEntry::TxnsByAccount(*account).into(), &serialize(&txns, Infinite).unwrap())
         } else { Ok(()) }
     }
 
@@ -865,11 +1158,11 @@ pub trait Database: Send + Sync {
 /// prevent conflicts between the different regions even if they are using non-secure hashing
 /// methods.
 ///
-/// TODO: Remove events older than we allow for a fork from network state
 /// TODO: Convert this to Shard and split of Network State?
 pub struct DatabaseImpl {
     db: DB,
-    head: HeadRef
+    head: HeadRef,
+    index_strategy: RecordKeeperIndexingStrategy
 }
 
 impl Database for DatabaseImpl {
@@ -878,6 +1171,12 @@ impl Database for DatabaseImpl {
         self.db.iterator(IteratorMode::Start).next().is_none()
     }
 
+    /// Which indexes and data this database keeps.
+    #[inline]
+    fn get_index_strategy(&self) -> RecordKeeperIndexingStrategy {
+        self.index_strategy
+    }
+
     /// Retrieve raw data from the database. Should have no uses outside this class.
     #[inline]
     fn _get(&self, key: Key) -> Result<Bin, Error> {
@@ -897,6 +1196,15 @@ impl Database for DatabaseImpl {
         Ok(())
     }
 
+    /// Retrieve every raw key/value pair in the network-state keyspace.
+    fn _get_network_entries(&self) -> Result<Vec<(Bin, Bin)>, Error> {
+        let prefix = Key::Network(NetworkEntry::Generic(Bin::new())).as_bin();
+        Ok(self.db.iterator(IteratorMode::From(&prefix, Direction::Forward))
+            .take_while(|&(ref k, _)| k.starts_with(&prefix))
+            .map(|(k, v)| (k.to_vec(), v.to_vec()))
+            .collect())
+    }
+
     /// Write a series of changes to the database. These writes are all or none. If an error occurs,
     /// the database is in the same state it was in beforehand.
     ///
@@ -905,7 +1213,15 @@ impl Database for DatabaseImpl {
     /// can be forced to happen somehow, then we will need to find an alternative to a panic.
     #[inline]
     fn apply(&mut self, wb: WriteBatch) -> Result<(), Error> {
-        self.db.write(wb)?;
+        let mut batch = RocksWriteBatch::default();
+        for (key, value) in wb {
+            match value {
+                Some(v) => batch.put(&key, &v)?,
+                None => batch.delete(&key)?
+            }
+        }
+
+        self.db.write(batch)?;
         self.head = deserialize(&self._get(CacheEntry::CurrentHead.into()).unwrap())
             .expect("HeadRef was not able to be retrieved from the database after applying changes.");
 
@@ -959,13 +1275,13 @@ impl Database for DatabaseImpl {
 
 impl DatabaseImpl {
     /// Create a new Database from a RocksDB instance
-    pub fn new(db: DB) -> DatabaseImpl {
+    pub fn new(db: DB, index_strategy: RecordKeeperIndexingStrategy) -> DatabaseImpl {
         let head = //attempt to read the current block
             if let Ok(value) = Self::get_raw_data_static(&db, CacheEntry::CurrentHead.into()) {
                 deserialize(&value).unwrap_or(HeadRef::default())
             } else { HeadRef::default() };
 
-        DatabaseImpl{ db, head }
+        DatabaseImpl{ db, head, index_strategy }
     }
 
     /// Open the RocksDB database based on the environment or by the given path. Construct a new
@@ -974,14 +1290,14 @@ impl DatabaseImpl {
     /// # Warning
     /// Any database which is opened, is assumed to contain data in a certain way, any outside
     /// modifications can cause undefined behavior.
-    pub fn open(path: PathBuf) -> Result<DatabaseImpl, RocksDBError> {
+    pub fn open(path: PathBuf, index_strategy: RecordKeeperIndexingStrategy) -> Result<DatabaseImpl, RocksDBError> {
         let mut options = Options::default();
         options.create_if_missing(true);
         options.set_compression_type(DBCompressionType::Lz4hc);
         options.increase_parallelism(num_cpus::get() as i32);
         Ok(
             DB::open_default(path)
-                .map(|db| Self::new(db))?
+                .map(|db| Self::new(db, index_strategy))?
         )
     }
 
@@ -1,11 +1,10 @@
 use bin::{Bin, AsBin};
 use bincode::{deserialize, serialize, Bounded, Infinite};
 use primitives::{U256, RawEvents, RawEvent, event, BoundingBox};
-use super::database::{PLOT_EVENT_BUCKET_SIZE, Database, HeadRef, UpIter, DownIter};
-use super::{Error, PlotID, key::*};
+use super::database::{PLOT_EVENT_BUCKET_SIZE, Database, HeadRef, UpIter, DownIter, WriteBatch};
+use super::{Error, PlotID, RecordKeeperIndexingStrategy, key::*};
 use super::error::map_not_found;
 use serde::de::DeserializeOwned;
-use rocksdb::WriteBatch;
 use std::collections::{BTreeSet, HashSet, HashMap};
 
 /// A snapshot of the network state at a given point in time. This builds on a reference to the
@@ -45,7 +44,7 @@ impl<'db> DBState<'db> {
     }
 
     /// Take the difference between this state and the underlying database and compile them into a
-    /// WriteBatch which may be applied atomically to RocksDB. The changes should only be applied
+    /// WriteBatch which may be applied atomically to the database. The changes should only be applied
     /// to the same database which is referenced by this state, and will make the database identical
     /// to this current state once done.
     pub fn compile(mut self) -> Result<WriteBatch, Error> {
@@ -54,10 +53,10 @@ impl<'db> DBState<'db> {
 
         // Handle all simple puts/dels
         for (key, value) in self.diff.new_values.into_iter() {
-            wb.put(&key.as_bin(), &value)?;
+            wb.put(&key.as_bin(), &value);
         }
         for key in self.diff.del_values.into_iter() {
-            wb.delete(&key.as_bin())?;
+            wb.delete(&key.as_bin());
         }
 
         // Now construct an iterator over all plots which yields the new events and removed events.
@@ -112,7 +111,7 @@ impl<'db> DBState<'db> {
                 wb.put(
                     &Key::Network(NetworkEntry::Plot(plot_id, tick)).as_bin(),
                     &empty_events
-                )?;
+                );
 
                 if tick < PLOT_EVENT_BUCKET_SIZE { break; }
                 tick -= PLOT_EVENT_BUCKET_SIZE;
@@ -158,7 +157,7 @@ impl<'db> DBState<'db> {
 
                 // Write the tick bucket
                 let key: Key = NetworkEntry::Plot(plot_id, tb_min).into();
-                wb.put(&key.as_bin(), &serialize(&events, Infinite).unwrap())?;
+                wb.put(&key.as_bin(), &serialize(&events, Infinite).unwrap());
             }
         }
 
@@ -174,6 +173,11 @@ impl<'db> Database for DBState<'db> {
         self.db.is_empty() && self.diff.is_empty()
     }
 
+    #[inline]
+    fn get_index_strategy(&self) -> RecordKeeperIndexingStrategy {
+        self.db.get_index_strategy()
+    }
+
     /// Retrieve a value first from the diff if it has been defined, and then from the database if
     /// not. This will return a NotFound Error if the value is not in the database or if it has been
     /// 'deleted' in the diff.
@@ -213,6 +217,10 @@ impl<'db> Database for DBState<'db> {
         unimplemented!("Cannot apply a write batch to a DBState object.")
     }
 
+    fn _get_network_entries(&self) -> Result<Vec<(Bin, Bin)>, Error> {
+        unimplemented!("Cannot list network entries of a DBState object.")
+    }
+
     fn _get_plot_event_bucket(&self, _plot_id: PlotID, _tick: u64) -> Result<Option<RawEvents>, Error> {
         unimplemented!("Cannot get an event bucket from a DBState object.")
     }
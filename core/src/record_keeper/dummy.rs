@@ -1,10 +1,11 @@
 use bin::Bin;
-use primitives::{U256, U160, U256_ZERO, Txn, Block, BlockHeader, HasBlockHeader};
+use primitives::{U256, U160, U256_ZERO, Txn, Block, BlockHeader, HasBlockHeader, ListenerPool};
 use std::collections::{HashMap, BTreeMap, HashSet};
 use std::sync::Arc;
 use std::sync::{RwLock,Mutex};
 use primitives::{RawEvents};
-use super::{RecordKeeper, RecordKeeperStatistics, Error, LogicError, PlotID};
+use super::{RecordKeeper, RecordKeeperStatistics, RecordEvent, Error, LogicError, PlotID};
+use futures::sync::mpsc::Sender;
 use super::BlockPackage;
 use time::Time;
 
@@ -15,7 +16,8 @@ struct BlockTreeNode {
     pub children: Mutex<Vec<Arc<BlockTreeNode>>>
 }
 
-/// A record keeper which maintains an in-memory database of blocks and transactions
+/// A record keeper which maintains an in-memory database of blocks and transactions. It does not
+/// validate anything or store plot events; use `RecordKeeperImpl::in_memory` when those are needed.
 pub struct DummyRecordKeeper {
     #[allow(dead_code)]
     blocks: Arc<BlockTreeNode>,
@@ -24,6 +26,8 @@ pub struct DummyRecordKeeper {
     pending_txns: RwLock<HashMap<U256, Txn>>,
 
     best_block: RwLock<Arc<BlockTreeNode>>,
+
+    record_listeners: Mutex<ListenerPool<RecordEvent>>,
 }
 
 impl DummyRecordKeeper {
@@ -55,8 +59,31 @@ impl DummyRecordKeeper {
             txns: RwLock::new(HashMap::new()),
             pending_txns: RwLock::new(HashMap::new()),
             best_block: RwLock::new(node),
+            record_listeners: Mutex::new(ListenerPool::new()),
         }
     }
+
+    /// Find the blocks which leave the current chain if `new` becomes the best block, starting with
+    /// the highest, along with the height of the block both chains share.
+    fn find_fork(&self, old: &Arc<BlockTreeNode>, new: &Arc<BlockTreeNode>) -> (Vec<Arc<BlockTreeNode>>, u64) {
+        let blocks = self.blocks_hashes.read().unwrap();
+        let parent = |n: &Arc<BlockTreeNode>| Arc::clone(blocks.get(&n.block.prev).unwrap());
+
+        let (mut a, mut b) = (Arc::clone(old), Arc::clone(new));
+        let mut undone = Vec::new();
+        while a.height > b.height {
+            undone.push(Arc::clone(&a));
+            a = parent(&a);
+        }
+        while b.height > a.height { b = parent(&b); }
+        while !Arc::ptr_eq(&a, &b) {
+            undone.push(Arc::clone(&a));
+            a = parent(&a);
+            b = parent(&b);
+        }
+
+        (undone, a.height)
+    }
 }
 
 impl RecordKeeper for DummyRecordKeeper {
@@ -102,7 +129,7 @@ impl RecordKeeper for DummyRecordKeeper {
     /// Add a new block and its associated transactions to the chain state after verifying
     /// it is valid. Also move the network state to be at the new end of the chain.
     /// Returns true if the block was added, false if it was already in the system.
-    fn add_block(&self, block: &Block, _fresh: bool) -> Result<bool, Error> {
+    fn add_block(&self, block: &Block, fresh: bool) -> Result<bool, Error> {
 
         if let Some(_) = self.blocks_hashes.read().unwrap().get(&block.calculate_hash()) {
             return Ok(false);
@@ -111,41 +138,64 @@ impl RecordKeeper for DummyRecordKeeper {
         let bh = self.blocks_hashes.read().unwrap().get(&block.prev).cloned();
 
         if let Some(node) = bh {
-            
-            let mut ptxns = self.pending_txns.write().unwrap();
-            let mut atxns = self.txns.write().unwrap();
+            let (uncled, invalidated) = {
+                let mut ptxns = self.pending_txns.write().unwrap();
+                let mut atxns = self.txns.write().unwrap();
+
+                // first, we must have all the transactions listed within the block in our db
+                for txn in block.txns.iter() {
+                    if ptxns.get(&txn).is_none() {
+                        return Err(Error::Logic(LogicError::MissingPrevious));
+                    }
+                }
 
-            // first, we must have all the transactions listed within the block in our db
-            for txn in block.txns.iter() {
-                if ptxns.get(&txn).is_none() {
-                    return Err(Error::Logic(LogicError::MissingPrevious));
+                // now we can start adding the block
+                for txn in block.txns.iter() {
+                    atxns.insert(*txn, ptxns.remove(txn).unwrap());
                 }
-            }
 
-            // now we can start adding the block
-            for txn in block.txns.iter() {
-                atxns.insert(*txn, ptxns.remove(txn).unwrap());
-            }
+                let h = block.calculate_hash();
+                let new_node = Arc::new(BlockTreeNode {
+                    block: block.clone(),
+                    height: node.height + 1,
+                    children: Mutex::new(Vec::new())
+                });
 
-            let h = block.calculate_hash();
-            let new_node = Arc::new(BlockTreeNode {
-                block: block.clone(),
-                height: node.height + 1,
-                children: Mutex::new(Vec::new())
-            });
+                {
+                    let mut childs = node.children.lock().unwrap();
+                    childs.push(Arc::clone(&new_node));
+                }
 
-            {
-                let mut childs = node.children.lock().unwrap();
-                childs.push(Arc::clone(&new_node));
-            }
-            {
                 let mut bb = self.best_block.write().unwrap();
-                if bb.height < new_node.height {
+                let uncled = bb.height >= new_node.height;
+                let mut invalidated = None;
+                if !uncled {
+                    // moving onto another branch undoes the blocks only the old one had
+                    if bb.block.calculate_hash() != block.prev {
+                        let (undone, after_height) = self.find_fork(&bb, &new_node);
+                        let after_tick = undone.iter()
+                            .flat_map(|n| n.block.txns.iter())
+                            .filter_map(|t| atxns.get(t))
+                            .map(|t| t.mutation.earliest_game_event())
+                            .min().unwrap_or(u64::max_value());
+                        invalidated = Some(RecordEvent::StateInvalidated {
+                            new_height: new_node.height,
+                            after_height,
+                            after_tick
+                        });
+                    }
                     *bb = Arc::clone(&new_node);
                 }
-            }
 
-            self.blocks_hashes.write().unwrap().insert(h, new_node); 
+                self.blocks_hashes.write().unwrap().insert(h, new_node);
+                (uncled, invalidated)
+            };
+
+            let mut record_listeners = self.record_listeners.lock().unwrap();
+            if let Some(e) = invalidated {
+                record_listeners.notify(&e);
+            }
+            record_listeners.notify(&RecordEvent::NewBlock { uncled, fresh, block: block.clone() });
 
             Ok(true)
         }
@@ -307,6 +357,12 @@ impl RecordKeeper for DummyRecordKeeper {
     fn get_txn_receive_time(&self, txn: U256) -> Result<Time, Error> {
         self.get_txn(&txn).map(|txn| txn.timestamp)
     }
+
+    /// Add a new listener for events such as new blocks. This will also take a moment to remove any
+    /// listeners which no longer exist.
+    fn register_record_listener(&self, listener: Sender<RecordEvent>) {
+        self.record_listeners.lock().unwrap().register(listener);
+    }
 }
 
 #[test]
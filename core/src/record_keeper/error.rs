@@ -11,6 +11,7 @@ pub enum Error {
     NotFound(DBKey), // when data is not found in the database (prefix, key, postfix).
     Deserialize(String), // when data cannot be deserialized
     OutOfMemory(String),
+    IndexDisabled(String), // when the indexing strategy does not store the data requested
     Logic(LogicError), // When something is wrong with a block, txn, or mutation
 }
 
@@ -21,6 +22,7 @@ impl StdErr for Error {
             Error::NotFound(_) => "Could not find the data requested at that Hash (may not be an issue).",
             Error::Deserialize(ref e) => e,
             Error::OutOfMemory(_) => "An internal memory limit was reached.",
+            Error::IndexDisabled(ref e) => e,
             Error::Logic(_) => "Something is not right with the block, txn, or mutations."
         }
     }
@@ -31,6 +33,7 @@ impl StdErr for Error {
             Error::NotFound(..) => None,
             Error::Deserialize(_) => None,
             Error::OutOfMemory(_) => None,
+            Error::IndexDisabled(_) => None,
             Error::Logic(ref e) => Some(e),
         }
     }
@@ -77,7 +80,12 @@ pub enum LogicError {
     UndoOrigin,
     UnrecognizedCreator,
     NotEnoughShares,
-    InvalidSigner
+    InvalidSigner,
+    AlreadySynced,
+    BeyondFinality,
+    BlockTooLarge,
+    InvalidEvent(String),
+    InvalidSnapshot(String)
 }
 
 impl StdErr for LogicError {
@@ -93,7 +101,12 @@ impl StdErr for LogicError {
             LogicError::UndoOrigin => "Cannot walk backwards past an origin block.",
             LogicError::UnrecognizedCreator => "The person who created and signed the block is unknown.",
             LogicError::NotEnoughShares => "The sender is trying to send more shares than he/she owns.",
-            LogicError::InvalidSigner => "This transaction requires a different person to have signed it."
+            LogicError::InvalidSigner => "This transaction requires a different person to have signed it.",
+            LogicError::AlreadySynced => "A snapshot can only be imported before syncing past genesis.",
+            LogicError::BeyondFinality => "This would reorg blocks which are already final.",
+            LogicError::BlockTooLarge => "The block exceeds the maximum size or number of txns.",
+            LogicError::InvalidEvent(_) => "The event could not be decoded or is not what was expected.",
+            LogicError::InvalidSnapshot(_) => "The snapshot is not consistent with the blocks or state it contains."
         }
     }
 
@@ -1,8 +1,11 @@
-use primitives::{Block, JBlock, Txn, JTxn, Event, RawEvent, JRawEvent};
+use primitives::{Block, JBlock, Txn, JTxn, Event, RawEvent, JRawEvent, U256, JU256, BoundingBox};
 use std::collections::BTreeSet;
+use std::marker::PhantomData;
 use std::mem::size_of;
-use super::PlotID;
+use super::{PlotID, RecordKeeper, Error, LogicError};
 use bincode;
+use futures::prelude::*;
+use futures::sync::mpsc::channel;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
@@ -21,6 +24,8 @@ pub enum RecordEvent {
     NewTxn { fresh: bool, txn: Txn },
     /// The state needs to be transitioned backwards, probably onto a new branch
     StateInvalidated { new_height: u64, after_height: u64, after_tick: u64 },
+    /// The child shards split off from this shard, or the areas they cover, have changed
+    ChildShards { children: Vec<(U256, BoundingBox)> },
 }
 impl Event for RecordEvent {}
 
@@ -78,12 +83,62 @@ impl<E> DePlotEvent<E> where E: Event + Serialize {
 
 
 
+/// Checks that the payload of a plot event can be understood by the application. Once registered
+/// with record keeper, txns with events which do not pass are turned away from the pending pool.
+pub trait EventCodec: Send + Sync {
+    fn check(&self, event: &PlotEvent) -> Result<(), Error>;
+}
+
+/// Typed encoding and decoding of the game events of an application.
+pub struct GameEventCodec<E>(PhantomData<fn() -> E>);
+
+impl<E> GameEventCodec<E> where E: Event + Serialize + DeserializeOwned {
+    pub fn new() -> GameEventCodec<E> {
+        GameEventCodec(PhantomData)
+    }
+
+    pub fn decode(&self, event: &PlotEvent) -> Result<DePlotEvent<E>, Error> {
+        DePlotEvent::deserialize(event)
+            .map_err(|e| LogicError::InvalidEvent(e.to_string()).into())
+    }
+
+    pub fn decode_raw(&self, event: &RawEvent) -> Result<E, Error> {
+        bincode::deserialize(event)
+            .map_err(|e| LogicError::InvalidEvent(e.to_string()).into())
+    }
+
+    pub fn encode(&self, event: &DePlotEvent<E>) -> Result<PlotEvent, Error> {
+        Ok(event.serialize()?)
+    }
+
+    pub fn encode_raw(&self, event: &E) -> Result<RawEvent, Error> {
+        Ok(bincode::serialize(event, bincode::Infinite)?)
+    }
+
+    /// Register a game listener with record keeper which receives the decoded events. Events which
+    /// cannot be decoded are skipped, though none should arrive if this codec is also registered.
+    pub fn register_listener(&self, rk: &RecordKeeper, buffer: usize) -> Box<Stream<Item=DePlotEvent<E>, Error=()> + Send> {
+        let (tx, rx) = channel(buffer);
+        rk.register_game_listener(tx);
+        Box::new(rx.filter_map(|e| DePlotEvent::deserialize(&e).ok()))
+    }
+}
+
+impl<E> EventCodec for GameEventCodec<E> where E: Event + Serialize + DeserializeOwned {
+    fn check(&self, event: &PlotEvent) -> Result<(), Error> {
+        self.decode(event).map(|_| ())
+    }
+}
+
+
+
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum JRecordEvent {
     NewBlock { uncled: bool, fresh: bool, block: JBlock },
     NewTxn { fresh: bool, txn: JTxn },
-    StateInvalidated { new_height: u64, after_height: u64, after_tick: u64}
+    StateInvalidated { new_height: u64, after_height: u64, after_tick: u64},
+    ChildShards { children: Vec<(JU256, BoundingBox)> }
 }
 
 impl From<RecordEvent> for JRecordEvent {
@@ -91,7 +146,8 @@ impl From<RecordEvent> for JRecordEvent {
         match e {
             RecordEvent::NewBlock{uncled, fresh, block} => JRecordEvent::NewBlock{uncled, fresh, block: block.into()},
             RecordEvent::NewTxn{fresh, txn} => JRecordEvent::NewTxn{fresh, txn: txn.into()},
-            RecordEvent::StateInvalidated{new_height, after_height, after_tick} => JRecordEvent::StateInvalidated{new_height, after_height, after_tick}
+            RecordEvent::StateInvalidated{new_height, after_height, after_tick} => JRecordEvent::StateInvalidated{new_height, after_height, after_tick},
+            RecordEvent::ChildShards{children} => JRecordEvent::ChildShards{children: children.into_iter().map(|(id, b)| (id.into(), b)).collect()}
         }
     }
 }
@@ -101,7 +157,8 @@ impl Into<RecordEvent> for JRecordEvent {
         match self {
             JRecordEvent::NewBlock{uncled, fresh, block} => RecordEvent::NewBlock{uncled, fresh, block: block.into()},
             JRecordEvent::NewTxn{fresh, txn} => RecordEvent::NewTxn{fresh, txn: txn.into()},
-            JRecordEvent::StateInvalidated{new_height, after_height, after_tick} => RecordEvent::StateInvalidated{new_height, after_height, after_tick}
+            JRecordEvent::StateInvalidated{new_height, after_height, after_tick} => RecordEvent::StateInvalidated{new_height, after_height, after_tick},
+            JRecordEvent::ChildShards{children} => RecordEvent::ChildShards{children: children.into_iter().map(|(id, b)| (id.into(), b)).collect()}
         }
     }
 }
@@ -1,5 +1,27 @@
 use std::collections::{HashMap, BTreeMap};
-use super::PlotID;
+use std::sync::Arc;
+use parking_lot::RwLock;
+use futures::prelude::*;
+use futures::sync::mpsc::channel;
+use primitives::{Event, RawEvents, U160};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use super::{PlotID, RecordKeeper, RecordEvent, GameEventCodec, Error, database::Database};
+
+/// The state of an application's game on a single plot. It must be deterministic, i.e. applying the
+/// same events in the same order must always give the same state, so that it can be rebuilt from the
+/// plot events stored by record keeper.
+pub trait GameState: Clone + Send + Sync + 'static {
+    type Event: Event + Serialize + DeserializeOwned;
+
+    /// The state of a plot before any events have occurred on it.
+    fn initial(plot: PlotID) -> Self;
+
+    /// Apply an event which occurred at the given tick. The player is the creator of the txn which
+    /// held the event; it is not stored with plot events so it will be `None` when replaying. The
+    /// state should be left unchanged if an error is returned.
+    fn apply(&mut self, tick: u64, event: Self::Event, player: Option<U160>) -> Result<(), Error>;
+}
 
 /// A cache of plot game states by tick
 pub struct GameStateCache<S> {
@@ -63,6 +85,7 @@ impl<S> GameStateCache<S> {
         } else {
             self.states = self.states.split_off(&tick);
         }
+        self.refresh_latest();
     }
 
     /// Drop all cached information for a given plot
@@ -70,11 +93,12 @@ impl<S> GameStateCache<S> {
         for (_, plots) in self.states.iter_mut() {
             plots.remove(&plot);
         }
+        self.latest.remove(&plot);
     }
 
     /// Drop any cached data after a certain point. If Plot is specified it will only drop things
     /// after the tick for a certain plot.
-    fn drop_after(&mut self, tick: u64, plot: Option<PlotID>) {
+    pub fn drop_after(&mut self, tick: u64, plot: Option<PlotID>) {
         if let Some(plot) = plot {
             for (t, plots) in self.states.iter_mut().rev() {
                 if *t <= tick { break; }
@@ -85,5 +109,160 @@ impl<S> GameStateCache<S> {
             self.states.split_off(&tick);
             if t.is_some() { self.states.insert(tick, t.unwrap()); }
         }
+        self.refresh_latest();
+    }
+
+    /// Drop any cached states which may have been built from events that are no longer part of
+    /// the chain, i.e. those at or after the `after_tick` of a `StateInvalidated` event.
+    pub fn invalidate(&mut self, event: &RecordEvent) {
+        if let &RecordEvent::StateInvalidated { after_tick, .. } = event {
+            if after_tick == 0 {
+                self.states.clear();
+                self.latest.clear();
+            } else {
+                self.drop_after(after_tick - 1, None);
+            }
+        }
+    }
+
+    /// Rebuild the latest tick of each plot after states have been dropped.
+    fn refresh_latest(&mut self) {
+        self.latest.clear();
+        for (t, plots) in self.states.iter() {
+            for plot in plots.keys() {
+                self.latest.insert(*plot, *t);
+            }
+        }
+    }
+}
+
+
+impl<S: Send + Sync + 'static> GameStateCache<S> {
+    /// Listen to record keeper and invalidate the cache whenever the network state is moved back.
+    /// The returned future must be run for this to happen and will resolve once record keeper drops
+    /// its listeners.
+    pub fn register_invalidator(cache: Arc<RwLock<GameStateCache<S>>>, rk: &RecordKeeper, buffer: usize) -> Box<Future<Item=(), Error=()> + Send> {
+        let (tx, rx) = channel(buffer);
+        rk.register_record_listener(tx);
+        Box::new(rx.for_each(move |e| {
+            cache.write().invalidate(&e);
+            Ok(())
+        }))
+    }
+}
+
+/// Replays plot events into game states and caches the results so that any application can query
+/// the state of a plot at a given tick. Plots are replayed from their first event, so record keeper
+/// must not use the `Light` indexing strategy which prunes old events.
+pub struct GameEngine<S: GameState> {
+    cache: Arc<RwLock<GameStateCache<S>>>,
+    codec: GameEventCodec<S::Event>
+}
+
+impl<S: GameState> GameEngine<S> {
+    pub fn new(cache: Arc<RwLock<GameStateCache<S>>>) -> GameEngine<S> {
+        GameEngine { cache, codec: GameEventCodec::new() }
+    }
+
+    pub fn cache(&self) -> &Arc<RwLock<GameStateCache<S>>> {
+        &self.cache
+    }
+
+    /// Get the state of a plot at the given tick, or the latest state if no tick is specified.
+    pub fn state_at(&self, rk: &RecordKeeper, plot: PlotID, tick: Option<u64>) -> Result<S, Error> {
+        self.replay(plot, tick, |from| rk.get_plot_events(plot, from))
+    }
+
+    /// Get the state of a plot at the given tick using the events in a database. This is for
+    /// mutation rules which need to look at the state being validated against.
+    pub fn state_in(&self, db: &Database, plot: PlotID, tick: Option<u64>) -> Result<S, Error> {
+        self.replay(plot, tick, |from| db.get_plot_events(plot, from))
+    }
+
+    /// Drop any cached states which were built from events that are no longer part of the chain.
+    pub fn invalidate(&self, event: &RecordEvent) {
+        self.cache.write().invalidate(event);
+    }
+
+    /// Start from the latest cached state at or before `tick` and apply the events which follow it.
+    fn replay<F>(&self, plot: PlotID, tick: Option<u64>, get_events: F) -> Result<S, Error>
+        where F: Fn(u64) -> Result<RawEvents, Error>
+    {
+        let (from, mut state) = match self.cache.read().latest(plot, tick) {
+            Some((t, s)) => (t + 1, s.clone()),
+            None => (0, S::initial(plot))
+        };
+
+        let mut last = None;
+        for (t, events) in get_events(from)? {
+            if tick.map_or(false, |tick| t > tick) { break; }
+            for event in events {
+                state.apply(t, self.codec.decode_raw(&event)?, None)?;
+            }
+            last = Some(t);
+        }
+
+        // only cache it if it does not replace later states of the plot
+        if let Some(last) = last {
+            let mut cache = self.cache.write();
+            if cache.latest(plot, None).map_or(true, |(l, _)| l < last) {
+                cache.cache(last, plot, state.clone());
+            }
+        }
+
+        Ok(state)
     }
-}
\ No newline at end of file
+}
+
+
+#[test]
+fn invalidated_by_fork() {
+    use std::collections::BTreeSet;
+    use bin::Bin;
+    use primitives::{Block, BlockHeader, Change, Coord, Mutation, Txn, U256, U256_ZERO};
+    use time::Time;
+    use super::{DummyRecordKeeper, PlotEvent};
+
+    let rk = DummyRecordKeeper::new();
+    let cache = Arc::new(RwLock::new(GameStateCache::<u64>::new()));
+    let invalidator = GameStateCache::register_invalidator(Arc::clone(&cache), &rk, 16);
+    let plot = Coord(0, 0);
+    let genesis = rk.get_current_block_hash();
+
+    // the current chain has an event at tick 5
+    let mut mutation = Mutation::new();
+    mutation.changes.push(Change::PlotEvent(PlotEvent { from: plot, to: BTreeSet::new(), tick: 5, event: Bin::new() }));
+    rk.add_pending_txn(Txn::new(U160::from(0), mutation), true).unwrap();
+    let a = rk.create_block().unwrap();
+    assert!(rk.add_block(&a, true).unwrap());
+
+    cache.write().cache(3, plot, 3);
+    cache.write().cache(5, plot, 5);
+
+    // a longer branch without the event replaces it
+    let fork_block = |prev: U256, ms: i64| Block {
+        header: BlockHeader {
+            version: 1,
+            timestamp: Time::from_milliseconds(ms),
+            shard: U256_ZERO,
+            prev,
+            merkle_root: Block::calculate_merkle_root(&Vec::new()),
+            blob: Bin::new()
+        },
+        txns: Vec::new()
+    };
+    let b1 = fork_block(genesis, 1);
+    let b2 = fork_block(b1.calculate_hash(), 2);
+    assert!(rk.add_block(&b1, true).unwrap());
+    assert_eq!(rk.get_current_block_hash(), a.calculate_hash());
+    assert!(rk.add_block(&b2, true).unwrap());
+    assert_eq!(rk.get_current_block_hash(), b2.calculate_hash());
+
+    // process the events which were sent
+    drop(rk);
+    invalidator.wait().unwrap();
+
+    let cache = cache.read();
+    assert_eq!(cache.latest(plot, None), Some((3, &3)));
+    assert_eq!(cache.latest(plot, Some(5)), Some((3, &3)));
+}
@@ -64,6 +64,12 @@ pub enum NetworkEntry {
     Plot(PlotID, u64),
     ValidatorKey(U160),
     ValidatorStake(U160),
+    ShardLock(U256),
+    ShardClaim(U256, U256),
+    SlashEvidence(U160, U256),
+    ChildShards,
+    ShardGenesis(U256),
+    ChildBlockRef(U256),
     AdminKeyID,
     Generic(Bin)
 }
@@ -75,6 +81,12 @@ impl AsBin for NetworkEntry {
             Plot(id, tick) => prefix(&prefix(b"PLT", id), &(tick / DB::PLOT_EVENT_BUCKET_SIZE)),
             ValidatorKey(k) => prefix(b"VKY", k),
             ValidatorStake(k) => prefix(b"VSK", k),
+            ShardLock(id) => prefix(b"SLK", id),
+            ShardClaim(shard, id) => prefix(&prefix(b"SCM", shard), id),
+            SlashEvidence(id, proof) => prefix(&prefix(b"SEV", id), proof),
+            ChildShards => Bin::from(b"CSHRD" as &[u8]),
+            ShardGenesis(id) => prefix(b"SGN", id),
+            ChildBlockRef(shard) => prefix(b"CBR", shard),
             AdminKeyID => Bin::from(b"ADMIN" as &[u8]),
             Generic(b) => b.clone()
         }
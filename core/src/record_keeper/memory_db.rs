@@ -0,0 +1,149 @@
+use bin::{Bin, AsBin};
+use bincode::{serialize, deserialize, Bounded};
+use primitives::U256;
+use std::collections::BTreeMap;
+use super::database::{Database, HeadRef, UpIter, DownIter, WriteBatch};
+use super::{Error, RecordKeeperIndexingStrategy, key::*};
+
+/// A database which is kept entirely in memory and lost once dropped. It stores data exactly as
+/// `DatabaseImpl` does, so record keeper behaves the same on top of it, which makes it useful for
+/// testing applications without needing RocksDB on disk.
+pub struct MemoryDatabase {
+    data: BTreeMap<Bin, Bin>,
+    head: HeadRef,
+    index_strategy: RecordKeeperIndexingStrategy
+}
+
+impl MemoryDatabase {
+    pub fn new(index_strategy: RecordKeeperIndexingStrategy) -> MemoryDatabase {
+        MemoryDatabase {
+            data: BTreeMap::new(),
+            head: HeadRef::default(),
+            index_strategy
+        }
+    }
+}
+
+impl Database for MemoryDatabase {
+    /// Check if there are no entries in the database.
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Which indexes and data this database keeps.
+    #[inline]
+    fn get_index_strategy(&self) -> RecordKeeperIndexingStrategy {
+        self.index_strategy
+    }
+
+    /// Retrieve raw data from the database. Should have no uses outside this class.
+    #[inline]
+    fn _get(&self, key: Key) -> Result<Bin, Error> {
+        self.data.get(&key.as_bin())
+            .cloned()
+            .ok_or(Error::NotFound(key))
+    }
+
+    /// Put raw data into the database. Should have no uses outside this class.
+    #[inline]
+    fn _put(&mut self, key: Key, data: &[u8]) -> Result<(), Error> {
+        self.data.insert(key.as_bin(), data.to_vec());
+        Ok(())
+    }
+
+    /// Delete a key from the database. Should have no uses outside this class.
+    #[inline]
+    fn _delete(&mut self, key: Key) -> Result<(), Error> {
+        self.data.remove(&key.as_bin());
+        Ok(())
+    }
+
+    /// Retrieve every raw key/value pair in the network-state keyspace.
+    fn _get_network_entries(&self) -> Result<Vec<(Bin, Bin)>, Error> {
+        let prefix = Key::Network(NetworkEntry::Generic(Bin::new())).as_bin();
+        Ok(self.data.range(prefix.clone()..)
+            .take_while(|&(k, _)| k.starts_with(&prefix))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect())
+    }
+
+    /// Write a series of changes to the database. Nothing can fail part way through, so the
+    /// changes are always applied in full.
+    fn apply(&mut self, wb: WriteBatch) -> Result<(), Error> {
+        for (key, value) in wb {
+            match value {
+                Some(v) => { self.data.insert(key, v); },
+                None => { self.data.remove(&key); }
+            }
+        }
+
+        self.head = deserialize(&self._get(CacheEntry::CurrentHead.into())?)?;
+        debug!("Applied multiple writes to DB; head is now ({}) of height {}.", self.head.block, self.head.height);
+        Ok(())
+    }
+
+    /// Iterate up the current chain, it will only follow the current chain and will end when either
+    /// it reaches the head, a database error occurs, or a block header is not found for a block we
+    /// know is part of the current chain.
+    fn iter_up<'a>(&'a self, start_height: u64) -> UpIter<'a> {
+        UpIter::new(self, start_height)
+    }
+
+    /// Iterate down a given chain, it will follow the `prev` references provided by `BlockHeader`s.
+    /// This will end either when it reaches genesis, a database error occurs, or a block header is
+    /// not found for a block we know comes before it.
+    fn iter_down<'a>(&'a self, start_block: U256) -> DownIter<'a> {
+        DownIter::new(self, start_block)
+    }
+
+    /// Get the hash of the current head of the blockchain as it lines up with the network state.
+    #[inline]
+    fn get_current_block_hash(&self) -> U256 {
+        self.head.block
+    }
+
+    /// Get the height of the current head of the blockchain.
+    #[inline]
+    fn get_current_block_height(&self) -> u64 {
+        self.head.height
+    }
+
+    /// Update the head reference and save it to the database. This should be used when the network
+    /// state is changed to represent the current block the state is at.
+    fn _update_current_block(&mut self, hash: U256, height: Option<u64>) -> Result<(), Error> {
+        let h = { // set the height value if it does not exist
+            if let Some(h) = height { h }
+            else { self.get_block_height(hash)? }
+        };
+
+        let href = HeadRef{height: h, block: hash};
+        self.head = href.clone();
+        self._put(CacheEntry::CurrentHead.into(), &serialize(&href, Bounded(40)).unwrap())
+    }
+}
+
+
+#[test]
+fn record_keeper_in_memory() {
+    use futures::{Future, Stream};
+    use futures::sync::mpsc::channel;
+    use signer::generate_private_key;
+    use super::{RecordKeeper, RecordEvent, test_record_keeper};
+
+    let rk = test_record_keeper(&generate_private_key(), 1 << 20, 16, 3600);
+    let genesis = rk.get_current_block_hash();
+    assert_eq!(rk.get_block(&genesis).unwrap().calculate_hash(), genesis);
+
+    let (tx, rx) = channel(8);
+    rk.register_record_listener(tx);
+
+    let block = rk.create_block().unwrap();
+    assert!(rk.add_block(&block, true).unwrap());
+    assert!(!rk.add_block(&block, true).unwrap());
+    assert_eq!(rk.get_current_block_hash(), block.calculate_hash());
+    assert_eq!(rk.get_block(&block.calculate_hash()).unwrap(), block);
+
+    drop(rk);
+    let events = rx.collect().wait().unwrap();
+    assert_eq!(events, vec![RecordEvent::NewBlock { uncled: false, fresh: true, block }]);
+}
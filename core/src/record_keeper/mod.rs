@@ -5,6 +5,8 @@ pub mod events;
 pub mod key;
 pub mod db_state;
 pub mod game_state;
+pub mod snapshot;
+pub mod memory_db;
 
 mod record_keeper;
 mod dummy;
@@ -12,14 +14,16 @@ mod rules;
 
 pub use self::block_package::BlockPackage;
 pub use self::error::*;
-pub use self::events::{PlotEvent, JPlotEvent, RecordEvent, DePlotEvent};
+pub use self::events::{PlotEvent, JPlotEvent, RecordEvent, JRecordEvent, DePlotEvent, EventCodec, GameEventCodec};
 pub use self::db_state::*;
 pub use self::record_keeper::*;
 pub use self::dummy::*;
 pub use self::rules::*;
 pub use self::key::*;
-pub use self::game_state::GameStateCache;
+pub use self::game_state::{GameStateCache, GameState, GameEngine};
+pub use self::snapshot::Snapshot;
 pub use self::database::Database;
+pub use self::memory_db::MemoryDatabase;
 
 
 use primitives;
@@ -1,15 +1,25 @@
-use bin::Bin;
-use primitives::{JU256, U256, U160, U160_ZERO, U256_ZERO, Txn, Block, BlockHeader, HasBlockHeader, Change, ListenerPool};
+use bin::{Bin, AsBin};
+use primitives::{JU256, U256, U160, U160_ZERO, U256_ZERO, Txn, Block, BlockHeader, BoundingBox, HasBlockHeader, Change, ListenerPool};
 use std::collections::{HashMap, BTreeMap, HashSet};
 use std::path::PathBuf;
+use std::sync::Arc;
 use parking_lot::{RwLock, Mutex};
 use primitives::{RawEvents, event, Mutation};
-use super::{BlockPackage, Error, RecordEvent, PlotEvent, PlotID, DBState, rules, BlockRule, TxnRule, MutationRule, MutationRules, database::*};
+use super::{BlockPackage, Snapshot, MemoryDatabase, Error, LogicError, Key, BlockchainEntry, NetworkEntry, map_not_found, RecordEvent, PlotEvent, EventCodec, PlotID, DBState, rules, MutationRule, BlockRule, BlockRules, TxnRules, MutationRules, database::*};
 use time::Time;
+use openssl::pkey::PKey;
+use forging::rewards::EPoSRewards;
+use forging::slashing::SlashEvidence;
 
 use futures::sync::mpsc::Sender;
 
-const MAX_PENDING_TXN_MEM: usize = 128*1024*1024; //128 MB
+/// Space `create_block` leaves in a block for the blob added by the forging mechanism.
+const BLOB_ALLOWANCE: u64 = 16*1024; //16 KB
+/// Space `create_block` leaves in a block for the txns the forging mechanism adds to reward the
+/// validators of the previous block and to anchor the heads of child shards.
+const REWARD_ALLOWANCE: u64 = 8*1024; //8 KB
+/// Number of txns `create_block` leaves room for the forging mechanism to add.
+const FORGED_TXNS: u64 = 2;
 
 
 #[derive(Debug)]
@@ -18,15 +28,60 @@ pub struct RecordKeeperConfig {
     /// way or another.
     pub pending_txn_limit: u64,
 
+    /// Maximum number of txns a single creator may have pending at once.
+    pub pending_txns_per_creator: usize,
+
+    /// Number of seconds a txn may wait in the pending pool before it is dropped.
+    pub pending_txn_ttl: u64,
+
+    /// Maximum size in bytes of the pool of orphan blocks, i.e. blocks which arrived before their
+    /// parent. The oldest orphans are dropped first.
+    pub orphan_block_limit: u64,
+
+    /// Number of seconds an orphan block is held waiting for its parent before it is dropped.
+    pub orphan_block_ttl: u64,
+
+    /// Maximum size of a block in bytes, including its txns.
+    pub max_block_size: u64,
+
+    /// Maximum number of txns which may be included in a single block.
+    pub max_block_txns: u64,
+
     /// To what extent should data be stored by record keeper?
     pub index_strategy: RecordKeeperIndexingStrategy,
 
+    /// Number of blocks below the head after which a block is final and may no longer be reorged.
+    /// In `Light` mode, the undo information and old plot events of final blocks are pruned.
+    pub finality_depth: u64,
+
+    /// Number of blocks back the forging mechanism looks to choose validators. Must match the EPoS
+    /// configuration so slash proofs can be checked.
+    pub validators_scan: u64,
+
+    /// The rules which record keeper should use to validate the structure of blocks. Start from
+    /// `default_block_rules()` and add to it if custom block-level checks are required. The size
+    /// limits above are always enforced.
+    pub block_rules: BlockRules,
+
+    /// The rules which record keeper should use to validate individual txns. Start from
+    /// `default_txn_rules()` and add to it if custom txn-level checks are required.
+    pub txn_rules: TxnRules,
+
     /// The custom mutation rules which record keeper should use to validate txns
     pub rules: MutationRules,
+
+    /// Headers of the other shards this node follows, used to verify cross-shard claims and child
+    /// block references. If not set, all claims and references are rejected.
+    pub shard_headers: Option<Arc<rules::mutation::ShardHeaders>>,
+
+    /// Shares which validators start out with. They are given to any validator listed here which
+    /// has never held shares, rather than being part of the genesis block, so they can be set
+    /// without changing the genesis hash.
+    pub genesis_stake: Vec<(U160, u64)>,
 }
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RecordKeeperIndexingStrategy {
     /// Full indexing capability, including all data needed for particapating in regular blockchain
     /// operations and caches to speed up performace.
@@ -37,11 +92,27 @@ pub enum RecordKeeperIndexingStrategy {
     Standard,
 
     /// Slimmed down database, not storing all blockchain data, just a minimum amount to be somewhat
-    /// informed
+    /// informed. State which is older than the finality depth is pruned, including old plot events,
+    /// so applications which replay plots from the first event (e.g. with `GameEngine`) cannot use it.
     Light
 }
 
 
+impl RecordKeeperIndexingStrategy {
+    /// Check if this strategy stores at least everything `other` does.
+    pub fn includes(self, other: RecordKeeperIndexingStrategy) -> bool {
+        use self::RecordKeeperIndexingStrategy::*;
+        match (self, other) {
+            (Full, _) => true,
+            (Standard, Full) => false,
+            (Standard, _) => true,
+            (Light, Light) => true,
+            (Light, _) => false
+        }
+    }
+}
+
+
 #[derive(Debug, Serialize)]
 /// RK Stats which can be sent via JSON on request.
 pub struct RecordKeeperStatistics {
@@ -94,6 +165,13 @@ pub trait RecordKeeper: Send + Sync {
         Ok(true)
     }
 
+    /// Add a new block as `add_block` does, using the given txns for any the block lists which are
+    /// not known yet. The txns never enter the pool of pending transactions, so they are not held
+    /// to its limits.
+    fn add_block_with_txns(&self, block: &Block, _txns: Vec<Txn>, fresh: bool) -> Result<bool, Error> {
+        self.add_block(block, fresh)
+    }
+
     /// Add a new transaction to the pool of pending transactions after validating it. Returns true
     /// if it was added successfully to pending transactions, and returns false if it is already in
     /// the list of pending transactions or accepted into the database..
@@ -134,6 +212,29 @@ pub trait RecordKeeper: Send + Sync {
         Ok(1)
     }
 
+    /// Get the child shards which have been split off from this shard along with the area each
+    /// covers.
+    fn get_child_shards(&self) -> Result<Vec<(U256, BoundingBox)>, Error> {
+        Ok(Vec::new())
+    }
+
+    /// Get the genesis header of a child shard which was split off from this shard.
+    fn get_shard_genesis(&self, id: &U256) -> Result<BlockHeader, Error> {
+        Err(Error::NotFound(NetworkEntry::ShardGenesis(*id).into()))
+    }
+
+    /// Get the latest block of a child shard which has been anchored into this shard, along with
+    /// its height on the child shard. This is the canonical fork of the child as far as this shard
+    /// is concerned.
+    fn get_child_block_ref(&self, _shard: &U256) -> Result<Option<(U256, u64)>, Error> {
+        Ok(None)
+    }
+
+    /// Check if a proof has already been used to slash a validator.
+    fn is_slash_evidence_used(&self, _id: &U160, _proof: &Bin) -> Result<bool, Error> {
+        Ok(false)
+    }
+
     /// Retrieve the current block hash which the network state represents.
     fn get_current_block_hash(&self) -> U256 {
         U256_ZERO
@@ -167,6 +268,12 @@ pub trait RecordKeeper: Send + Sync {
         Ok(vec![])
     }
 
+    /// Get the block headers of the current chain from `from_height` to `to_height` inclusive, in
+    /// order of height.
+    fn get_blocks_range(&self, _from_height: u64, _to_height: u64) -> Result<Vec<BlockHeader>, Error> {
+        Ok(vec![])
+    }
+
     /// This is designed to get blocks between a start and end hash. It will get blocks from
     /// (last_known, target]. Do not include last-known because it is clearly already in the system,
     /// but do include the target block since it has not yet been accepted into the database.
@@ -174,6 +281,12 @@ pub trait RecordKeeper: Send + Sync {
         Ok(BlockPackage::new_empty())
     }
 
+    /// Get up to `limit` block headers from (last_known, target] without their txns. This is used
+    /// to serve light clients which only follow the chain of headers.
+    fn get_headers_between(&self, _last_known: &U256, _target: &U256, _limit: usize) -> Result<Vec<BlockHeader>, Error> {
+        Ok(Vec::new())
+    }
+
     /// Returns a map of events for each tick that happened after a given tick. Note: it will not
     /// seek to reconstruct old history so `from_tick` simply allows additional filtering, e.g. if
     /// you set `from_tick` to 0, you would not get all events unless the oldest events have not
@@ -190,6 +303,10 @@ pub trait RecordKeeper: Send + Sync {
     /// which no longer exist.
     fn register_game_listener(&self, _listener: Sender<PlotEvent>) {}
 
+    /// Set the codec used to check the events of new pending txns. Use a `GameEventCodec` of the
+    /// application's event type.
+    fn register_event_codec(&self, _codec: Arc<EventCodec>) {}
+
     /// Check if a block is valid and all its components.
     fn is_valid_block(&self, _block: &Block) -> Result<(), Error> {
         Ok(())
@@ -221,6 +338,11 @@ pub trait RecordKeeper: Send + Sync {
         Ok(Txn::new(U160_ZERO, Mutation::new()))
     }
 
+    /// Find which of the given txns are neither pending nor in the database.
+    fn get_missing_txns(&self, _hashes: &[U256]) -> Vec<U256> {
+        Vec::new()
+    }
+
     /// Whether or not the block is part of the longest chain, and therefore influences the history
     fn is_block_in_current_chain(&self, _hash: &U256) -> Result<bool, Error> {
         Ok(true)
@@ -236,10 +358,31 @@ pub trait RecordKeeper: Send + Sync {
         Ok(HashSet::new())
     }
 
+    /// Get up to `limit` txns created by an account which are part of the current chain, newest
+    /// first, along with the height of their block and their index within it. Only txns before the
+    /// given `(height, index)` position are listed. Pending txns are left out.
+    fn get_account_txns_before(&self, _account: &U160, _before: Option<(u64, usize)>, _limit: usize) -> Result<Vec<(u64, usize, U256)>, Error> {
+        Ok(vec![])
+    }
+
     /// Get the time a txn was originally received.
     fn get_txn_receive_time(&self, _txn: U256) -> Result<Time, Error> {
         Ok(Time::from_milliseconds(0))
     }
+
+    /// Export a snapshot of the network state at the block of the current chain at `height`, signed
+    /// with the given key. The snapshot will include `history` blocks before it. Only final blocks
+    /// may be snapshotted, since those imported from a snapshot can never be undone.
+    fn export_snapshot(&self, _height: u64, _history: u64, _key: &PKey) -> Result<Snapshot, Error> {
+        Err(Error::IndexDisabled("Snapshots are not supported by this record keeper".into()))
+    }
+
+    /// Replace the network state with a snapshot signed by `trusted_key`, after which only blocks
+    /// following the snapshot need to be synced. This is only allowed before syncing past genesis.
+    /// Returns the hash of the new head.
+    fn import_snapshot(&self, _snapshot: Snapshot, _trusted_key: &PKey) -> Result<U256, Error> {
+        Err(Error::IndexDisabled("Snapshots are not supported by this record keeper".into()))
+    }
 }
 
 
@@ -256,9 +399,13 @@ pub struct RecordKeeperImpl<DB> {
     db: RwLock<DB>,
     pending_txns: RwLock<HashMap<U256, (Time, Txn)>>,
 
+    /// Blocks whose parent is not known yet along with when they arrived and whether they were fresh
+    orphans: Mutex<HashMap<U256, (Time, Block, bool)>>,
+
     record_listeners: Mutex<ListenerPool<RecordEvent>>,
     game_listeners: Mutex<ListenerPool<PlotEvent>>,
 
+    event_codec: RwLock<Option<Arc<EventCodec>>>,
 }
 
 impl<DB: Database> RecordKeeper for RecordKeeperImpl<DB> {
@@ -278,6 +425,8 @@ impl<DB: Database> RecordKeeper for RecordKeeperImpl<DB> {
     }
 
     /// Use pending transactions to create a new block which can then be added to the network.
+    /// Pending txns are included oldest first until the block is full, leaving some room for the
+    /// blob.
     /// The block provided is complete except:
     /// 1. The proof of work/proof of stake mechanism has not been completed
     /// 2. The signature has not been applied to the block
@@ -288,105 +437,75 @@ impl<DB: Database> RecordKeeper for RecordKeeperImpl<DB> {
         let cbh = db.get_current_block_header()?;
         let cbh_h = cbh.calculate_hash();
 
-        let txns = pending_txns.keys().cloned().collect();
-
-        let block = Block {
+        let mut block = Block {
             header: BlockHeader {
                 version: 1,
                 timestamp: Time::current(),
                 shard: if cbh.shard.is_zero() { cbh_h } else { cbh.shard },
                 prev: cbh_h,
-                merkle_root: Block::calculate_merkle_root(&txns),
+                merkle_root: U256_ZERO,
                 blob: Bin::new()
             },
-            txns
+            txns: Vec::new()
         };
 
+        // stop at the first txn which does not fit since later ones may depend on it
+        let limit = self.config.max_block_size.saturating_sub(BLOB_ALLOWANCE + REWARD_ALLOWANCE);
+        let mut size = BlockPackage::header_size(&block) as u64;
+        block.txns = pending_order(&*pending_txns).into_iter()
+            .take(self.config.max_block_txns.saturating_sub(FORGED_TXNS) as usize)
+            .take_while(|&(_, _, txn)| {
+                size += txn.calculate_size() as u64 + 2;
+                size <= limit
+            })
+            .map(|(hash, _, _)| hash)
+            .collect();
+        block.merkle_root = Block::calculate_merkle_root(&block.txns);
+
         Ok(block)
     }
 
     /// Add a new block and its associated transactions to the chain state after verifying
     /// it is valid. Also move the network state to be at the new end of the chain.
     /// Returns true if the block was added, false if it was already in the system.
+    ///
+    /// If the parent of the block is not known, the block is held as an orphan and added once the
+    /// parent arrives; the `NotFound` error is still returned so the caller can go looking for it.
     fn add_block(&self, block: &Block, fresh: bool) -> Result<bool, Error> {
-        let hash = block.calculate_hash();
-        if self.get_block(&hash).is_ok() {
-            return Ok(false); // block already exists
-        }
-
-        self.is_valid_block(block)?;
-
-        let mut pending_txns = self.pending_txns.write();
-        let mut db = self.db.write();
-
-        // get around the scope issues
-        let initial_height = db.get_current_block_height();
-        let (invalidated_blocks, earliest_invalidated_tick, uncled);
-
-        // construct a write batch of all changes we are making using a DBState object.
-        let wb = {  // because consuming state with the compile operation is not enough for the borrow checker...
-            let mut state = DBState::new(&*db);
-
-            // we know it is a valid block, so go ahead and add it and then its transactions
-            if !state.add_block(block)? {
-                // between checking if the block was valid and taking out a write lock, the block
-                // has already been added.
-                return Ok(false);
-            }
+        self.add_block_with_txns(block, Vec::new(), fresh)
+    }
 
-            for txn_hash in block.txns.iter() {
-                if let Some((recv_time, txn)) = pending_txns.remove(txn_hash) { // we will need to add it
-                    state.add_txn(&txn, recv_time)?;
-                } else {
-                    // should already be in the DB then because otherwise is_valid_block should give an
-                    // error, so use an assert check
-                    assert!(state.get_txn(*txn_hash).is_ok())
+    /// Add a new block as `add_block` does, using the given txns for any the block lists which are
+    /// not known yet. The txns never enter the pool of pending transactions, so they are not held
+    /// to its limits.
+    fn add_block_with_txns(&self, block: &Block, txns: Vec<Txn>, fresh: bool) -> Result<bool, Error> {
+        let time = Time::current();
+        let txns: HashMap<U256, (Time, Txn)> = txns.into_iter()
+            .map(|txn| (txn.calculate_hash(), (time, txn)))
+            .collect();
+
+        match self.connect_block(block, &txns, fresh) {
+            Ok(true) => {
+                self.connect_orphans(block.calculate_hash());
+                Ok(true)
+            },
+            Err(Error::NotFound(Key::Blockchain(BlockchainEntry::BlockHeader(missing)))) => {
+                if missing == block.prev {
+                    self.add_orphan(block, fresh);
                 }
-            }
-
-            {
-                let (a, b) = state.walk_to_head()?;
-                invalidated_blocks = a;
-                earliest_invalidated_tick = b;
-            }
-
-            uncled = hash != state.get_current_block_hash();
-
-            // couple of quick checks...
-            // if uncled, basic verification that we have not moved
-            debug_assert!(!uncled || (
-                invalidated_blocks == 0 &&
-                    initial_height == state.get_current_block_height()
-            ));
-            // if not uncled, do some validity checks to make sure we moved correctly
-            debug_assert!(uncled || (
-                initial_height > invalidated_blocks &&
-                    initial_height < state.get_current_block_height()
-            ));
-
-            // write the changes to the actual db
-            state.compile()?
-        };
-
-        db.apply(wb)?;
-
-        // send out events as needed
-        let mut record_listeners = self.record_listeners.lock();
-        if invalidated_blocks > 0 {
-            record_listeners.notify(&RecordEvent::StateInvalidated {
-                new_height: db.get_current_block_height(),
-                after_height: initial_height - invalidated_blocks,
-                after_tick: earliest_invalidated_tick
-            });
+                Err(Error::NotFound(BlockchainEntry::BlockHeader(missing).into()))
+            },
+            r => r
         }
-        record_listeners.notify(&RecordEvent::NewBlock { uncled, fresh, block: block.clone() });
-
-        Ok(true)
     }
 
     /// Add a new transaction to the pool of pending transactions after validating it. Returns true
     /// if it was added successfully to pending transactions, and returns false if it is already in
     /// the list of pending transactions or accepted into the database..
+    ///
+    /// If the pool is full, the txn is refused since it is newer than everything already pending
+    /// and would be the first to go. A creator may only have `pending_txns_per_creator` txns
+    /// pending at once. Txns made by forgers to reward or slash validators are exempt from both.
     fn add_pending_txn(&self, txn: Txn, fresh: bool) -> Result<bool, Error> {
         let hash = txn.calculate_hash();
 
@@ -395,15 +514,21 @@ impl<DB: Database> RecordKeeper for RecordKeeperImpl<DB> {
             return Ok(false);
         }
 
+        // make sure the application can understand the events
+        if let Some(ref codec) = *self.event_codec.read() {
+            for change in txn.mutation.changes.iter() {
+                if let &Change::PlotEvent(ref e) = change {
+                    codec.check(e)?;
+                }
+                for e in change.claimed_events() {
+                    codec.check(&e)?;
+                }
+            }
+        }
+
         let mut pending_txns = self.pending_txns.write();
         let db = self.db.read();
 
-        let pending_size = pending_txns.values()
-            .fold(0, |acc, &(_, ref t)| acc + (t.calculate_size()));
-        if pending_size + txn.calculate_size() > MAX_PENDING_TXN_MEM {
-            return Err(Error::OutOfMemory("Maximum pending txn memory reached.".into()));
-        }
-
         // check if it is already in the database
         match db.get_txn(hash) {
             Ok(_) => return Ok(false),
@@ -411,9 +536,29 @@ impl<DB: Database> RecordKeeper for RecordKeeperImpl<DB> {
             Err(e) => return Err(e)
         }
 
-        debug!("New pending txn ({})", txn.calculate_hash());
+        let size = txn.calculate_size();
+        if size as u64 > self.config.pending_txn_limit {
+            return Err(Error::OutOfMemory("Txn is larger than the pending txn pool.".into()));
+        }
+
+        self.expire_pending_txns(&mut *pending_txns);
+
+        if !is_forged_txn(&txn) {
+            let from_creator = pending_txns.values()
+                .filter(|&&(_, ref t)| t.creator == txn.creator && !is_forged_txn(t))
+                .count();
+            if from_creator >= self.config.pending_txns_per_creator {
+                return Err(Error::OutOfMemory("Creator has too many pending txns.".into()));
+            }
+
+            let used = pending_txns.values().fold(0, |acc, &(_, ref t)| acc + t.calculate_size());
+            if (used + size) as u64 > self.config.pending_txn_limit {
+                return Err(Error::OutOfMemory("The pending txn pool is full.".into()));
+            }
+        }
+
+        debug!("New pending txn ({})", hash);
 
-        // add the event
         self.is_valid_txn_given_lock(&*db, &*pending_txns, &txn)?;
         pending_txns.insert(hash, (Time::current(), txn.clone()));
 
@@ -425,6 +570,9 @@ impl<DB: Database> RecordKeeper for RecordKeeperImpl<DB> {
                 &Change::PlotEvent(ref e) => {
                     game_listeners.notify(e);
                 }
+                &Change::ShardClaim{..} => for e in change.claimed_events() {
+                    game_listeners.notify(&e);
+                },
                 _ => (),
             }
         }
@@ -441,12 +589,32 @@ impl<DB: Database> RecordKeeper for RecordKeeperImpl<DB> {
 
     /// Get the shares of a validator given their ID.
     /// TODO: Handle shard-based shares
-    fn get_validator_stake(&self, _id: &U160) -> Result<u64, Error> {
-        // temp, come back to this later
-        /*self.db.read()
-            .get_validator_stake(*id)*/
+    fn get_validator_stake(&self, id: &U160) -> Result<u64, Error> {
+        // a validator who has never received shares simply has none
+        map_not_found(self.db.read().get_validator_stake(*id), 0)
+    }
 
-        Ok(1)
+    /// Get the child shards which have been split off from this shard along with the area each
+    /// covers.
+    fn get_child_shards(&self) -> Result<Vec<(U256, BoundingBox)>, Error> {
+        self.db.read().get_child_shards()
+    }
+
+    /// Get the genesis header of a child shard which was split off from this shard.
+    fn get_shard_genesis(&self, id: &U256) -> Result<BlockHeader, Error> {
+        self.db.read().get_shard_genesis(id)
+    }
+
+    /// Get the latest block of a child shard which has been anchored into this shard, along with
+    /// its height on the child shard. This is the canonical fork of the child as far as this shard
+    /// is concerned.
+    fn get_child_block_ref(&self, shard: &U256) -> Result<Option<(U256, u64)>, Error> {
+        self.db.read().get_child_block_ref(shard)
+    }
+
+    /// Check if a proof has already been used to slash a validator.
+    fn is_slash_evidence_used(&self, id: &U160, proof: &Bin) -> Result<bool, Error> {
+        self.db.read().is_slash_evidence_used(id, proof)
     }
 
     /// Import a package of blocks and transactions. Returns the hash of the last block imported.
@@ -466,92 +634,13 @@ impl<DB: Database> RecordKeeper for RecordKeeperImpl<DB> {
         }
 
         let last = blocks.last().unwrap().calculate_hash();
+        let hashes: Vec<U256> = blocks.iter().map(|b| b.calculate_hash()).collect();
 
-        // Lock the state as we make verify and plan (and eventually make) changes
-        let mut pending_txns = self.pending_txns.write();
-        let mut db = self.db.write();
-
-        let initial_height = db.get_current_block_height();
-        let initial_block = db.get_current_block_hash();
-
-        // get around scope issues.
-        let (invalidated_blocks, earliest_invalidated_tick);
-
-        let wb = {
-            let mut state = DBState::new(&*db);
-
-            // Add all blocks and associated transactions and verify they are valid
-            for block in blocks.iter() {
-                let block_hash = block.calculate_hash();
-                if state.get_block_header(&block_hash).is_ok() {
-                    // the block has already in the system.
-                    continue;
-                }
-
-                { // Check if it is valid
-                    // Yipee for second level differences!
-                    // (I just new making state implement DB would be worthwhile...)
-                    // TODO: should we walk our own state forward to reduce how far subsequent blocks have to walk to get to the same place?
-                    let prior_block_state = DBState::new(&state).at(block.prev)?;
-                    self.is_valid_block_given_state(&prior_block_state, &txns, &block)?;
-                }
-
-                let added = state.add_block(&block)?;
-                debug_assert!(added); // already verified it was not present
-
-                for txn_hash in block.txns.iter() {
-                    let txn = txns.get(txn_hash)
-                        .expect("Missing transaction after validating block from block package.");
-                    state.add_txn(&txn.1, time)?;
-                }
-
-                state.walk_to_head()?;
-            }
-
-            let (_undone_block, new_blocks, earliest_tick) =
-                state.calculate_invalidations_to_block(&initial_block)?;
-            invalidated_blocks = new_blocks;
-            earliest_invalidated_tick = earliest_tick;
-
-            state.compile()?
-        };
-
-        // Write the changes
-        db.apply(wb)?;
-        let db = db.downgrade();
-
-        // Validate all pending transactions against the new state
-        {
-            use std::mem::swap;
-
-            let mut txns = HashMap::with_capacity(pending_txns.len());
-            swap(&mut txns, &mut *pending_txns);
-
-            for (txn_hash, (recv_time, txn)) in txns {
-                if self.is_valid_txn_given_lock(&*db, &*pending_txns, &txn).is_err() {
-                    continue;
-                }
-                // else
-                pending_txns.insert(txn_hash, (recv_time, txn));
-            }
-
-            drop(pending_txns);
-        }
-
-        // Notify listeners of the changes
-        let mut record_listeners = self.record_listeners.lock();
-
-        for block in blocks {
-            let uncled = db.is_part_of_current_chain(block.calculate_hash())?;
-            record_listeners.notify(&RecordEvent::NewBlock { uncled, fresh: false, block });
-        }
+        self.import_blocks(blocks, txns, time)?;
 
-        if invalidated_blocks > 0 {
-            record_listeners.notify(&RecordEvent::StateInvalidated {
-                new_height: db.get_current_block_height(),
-                after_height: initial_height - invalidated_blocks,
-                after_tick: earliest_invalidated_tick
-            });
+        // orphans may have been waiting on any of the blocks in the package
+        for hash in hashes {
+            self.connect_orphans(hash);
         }
 
         Ok(last)
@@ -597,6 +686,13 @@ impl<DB: Database> RecordKeeper for RecordKeeperImpl<DB> {
         db.get_latest_blocks(count)
     }
 
+    /// Get the block headers of the current chain from `from_height` to `to_height` inclusive, in
+    /// order of height.
+    fn get_blocks_range(&self, from_height: u64, to_height: u64) -> Result<Vec<BlockHeader>, Error> {
+        let db = self.db.read();
+        db.get_blocks_range(from_height, to_height)
+    }
+
     /// This is designed to get blocks between a start and end hash. It will get blocks from
     /// (last_known, target]. Do not include last-known because it is clearly already in the system,
     /// but do include the target block since it has not yet been accepted into the database.
@@ -606,6 +702,12 @@ impl<DB: Database> RecordKeeper for RecordKeeperImpl<DB> {
         BlockPackage::blocks_between(&*db, last_known, target, limit)
     }
 
+    /// Get up to `limit` block headers from (last_known, target] without their txns. This is used
+    /// to serve light clients which only follow the chain of headers.
+    fn get_headers_between(&self, last_known: &U256, target: &U256, limit: usize) -> Result<Vec<BlockHeader>, Error> {
+        self.db.read().get_blocks_between(last_known, target, limit)
+    }
+
     /// Returns a map of events for each tick that happened after a given tick. Note: it will not
     /// seek to reconstruct old history so `from_tick` simply allows additional filtering, e.g. if
     /// you set `from_tick` to 0, you would not get all events unless the oldest events have not
@@ -620,7 +722,12 @@ impl<DB: Database> RecordKeeper for RecordKeeperImpl<DB> {
         for &(_, ref txn) in txns.values() {
             for change in &txn.mutation.changes {
                 if let &Change::PlotEvent(ref e) = change {
-                    if e.tick >= from_tick && (e.from == plot_id) || (e.to.contains(&plot_id)) {
+                    if e.tick >= from_tick && (e.from == plot_id || e.to.contains(&plot_id)) {
+                        event::add_event(&mut events, e.tick, e.event.clone());
+                    }
+                }
+                for e in change.claimed_events() {
+                    if e.tick >= from_tick && (e.from == plot_id || e.to.contains(&plot_id)) {
                         event::add_event(&mut events, e.tick, e.event.clone());
                     }
                 }
@@ -642,6 +749,12 @@ impl<DB: Database> RecordKeeper for RecordKeeperImpl<DB> {
         self.game_listeners.lock().register(listener);
     }
 
+    /// Set the codec used to check the events of new pending txns. Use a `GameEventCodec` of the
+    /// application's event type.
+    fn register_event_codec(&self, codec: Arc<EventCodec>) {
+        *self.event_codec.write() = Some(codec);
+    }
+
     /// Check if a block is valid and all its components.
     fn is_valid_block(&self, block: &Block) -> Result<(), Error> {
         let pending = self.pending_txns.read();
@@ -686,6 +799,16 @@ impl<DB: Database> RecordKeeper for RecordKeeperImpl<DB> {
         }
     }
 
+    /// Find which of the given txns are neither pending nor in the database.
+    fn get_missing_txns(&self, hashes: &[U256]) -> Vec<U256> {
+        let pending = self.pending_txns.read();
+        let db = self.db.read();
+        hashes.iter()
+            .filter(|h| !pending.contains_key(h) && db.get_txn(**h).is_err())
+            .cloned()
+            .collect()
+    }
+
     /// Whether or not the block is part of the longest chain, and therefore influences the history
     fn is_block_in_current_chain(&self, hash: &U256) -> Result<bool, Error> {
         let db = self.db.read();
@@ -715,6 +838,13 @@ impl<DB: Database> RecordKeeper for RecordKeeperImpl<DB> {
         Ok(txns)
     }
 
+    /// Get up to `limit` txns created by an account which are part of the current chain, newest
+    /// first, along with the height of their block and their index within it. Only txns before the
+    /// given `(height, index)` position are listed. Pending txns are left out.
+    fn get_account_txns_before(&self, account: &U160, before: Option<(u64, usize)>, limit: usize) -> Result<Vec<(u64, usize, U256)>, Error> {
+        self.db.read().get_account_txns_before(account, before, limit)
+    }
+
     /// Get the time a txn was originally received.
     fn get_txn_receive_time(&self, txn: U256) -> Result<Time, Error> {
         if let Some(&(time, _)) = self.pending_txns.read().get(&txn) {
@@ -722,23 +852,83 @@ impl<DB: Database> RecordKeeper for RecordKeeperImpl<DB> {
         }
         self.db.read().get_txn_receive_time(txn)
     }
-}
 
+    /// Export a snapshot of the network state at the block of the current chain at `height`, signed
+    /// with the given key. The snapshot will include `history` blocks before it. Only final blocks
+    /// may be snapshotted, since those imported from a snapshot can never be undone.
+    fn export_snapshot(&self, height: u64, history: u64, key: &PKey) -> Result<Snapshot, Error> {
+        let db = self.db.read();
+        if height.saturating_add(self.config.finality_depth) > db.get_current_block_height() {
+            return Err(LogicError::InvalidSnapshot("Snapshots can only be taken of final blocks".into()).into());
+        }
 
-impl RecordKeeperImpl<DatabaseImpl> {
-    /// Construct a new RecordKeeper by opening a database. This will create a new database if the
-    /// one specified does not exist.
-    /// # Warning
-    /// Any database which is opened, is assumed to contain data in a certain way, any outside
-    /// modifications can cause undefined behavior.
-    pub fn open(path: PathBuf, config: RecordKeeperConfig, genesis: (Block, Vec<Txn>)) -> Result<Self, Error> {
-        info!("Opening a RecordKeeper object with path '{:?}'", path);
-        let db = DatabaseImpl::open(path)?;
-        let rk = Self::new(db, config);
+        Snapshot::create(&*db, height, history, key)
+    }
 
-        { // Handle Genesis
-            let mut db = rk.db.write();
-            let wb = if db.is_empty() { // add genesis
+    /// Replace the network state with a snapshot signed by `trusted_key`, after which only blocks
+    /// following the snapshot need to be synced. This is only allowed before syncing past genesis.
+    /// Returns the hash of the new head.
+    fn import_snapshot(&self, snapshot: Snapshot, trusted_key: &PKey) -> Result<U256, Error> {
+        snapshot.verify(trusted_key)?;
+
+        let mut pending_txns = self.pending_txns.write();
+        let mut db = self.db.write();
+
+        if db.get_current_block_height() > 1 { // genesis is height 1
+            return Err(LogicError::AlreadySynced.into());
+        }
+
+        let wb = snapshot.compile(&*db)?;
+        db.apply(wb)?;
+        info!("Imported snapshot at ({}) of height {}.", db.get_current_block_hash(), snapshot.height);
+
+        // anything pending was validated against genesis
+        pending_txns.clear();
+
+        self.record_listeners.lock().notify(&RecordEvent::StateInvalidated {
+            new_height: snapshot.height,
+            after_height: 0,
+            after_tick: 0
+        });
+
+        Ok(db.get_current_block_hash())
+    }
+}
+
+
+impl RecordKeeperImpl<DatabaseImpl> {
+    /// Construct a new RecordKeeper by opening a database. This will create a new database if the
+    /// one specified does not exist.
+    /// # Warning
+    /// Any database which is opened, is assumed to contain data in a certain way, any outside
+    /// modifications can cause undefined behavior.
+    pub fn open(path: PathBuf, config: RecordKeeperConfig, genesis: (Block, Vec<Txn>)) -> Result<Self, Error> {
+        info!("Opening a RecordKeeper object with path '{:?}'", path);
+        let db = DatabaseImpl::open(path, config.index_strategy)?;
+        Self::with_genesis(db, config, genesis)
+    }
+}
+
+
+impl RecordKeeperImpl<MemoryDatabase> {
+    /// Construct a new RecordKeeper which keeps everything in memory. It runs the same rules and
+    /// sends the same events as one backed by RocksDB, but nothing is written to disk.
+    pub fn in_memory(config: RecordKeeperConfig, genesis: (Block, Vec<Txn>)) -> Result<Self, Error> {
+        let db = MemoryDatabase::new(config.index_strategy);
+        Self::with_genesis(db, config, genesis)
+    }
+}
+
+
+impl<DB: Database> RecordKeeperImpl<DB> {
+    /// Construct a new RecordKeeper on top of a database, adding the genesis block if the database
+    /// is empty or making sure it is the same genesis otherwise.
+    fn with_genesis(db: DB, config: RecordKeeperConfig, genesis: (Block, Vec<Txn>)) -> Result<Self, Error> {
+        let rk = Self::new(db, config);
+
+        { // Handle Genesis
+            let mut db = rk.db.write();
+            let wb = if db.is_empty() { // add genesis
                 debug!("Loaded DB is empty, adding genesis block...");
                 let mut state = DBState::new(&*db);
                 for ref txn in genesis.1 {
@@ -756,14 +946,18 @@ impl RecordKeeperImpl<DatabaseImpl> {
             if let Some(wb) = wb {
                 db.apply(wb)?;
             }
+
+            // seed the initial validators, which also covers databases made before they were seeded
+            for &(id, amount) in &rk.config.genesis_stake {
+                if let Err(Error::NotFound(..)) = db.get_validator_stake(id) {
+                    db._change_validator_stake(id, amount as i64)?;
+                }
+            }
         }
 
         Ok(rk)
     }
-}
-
 
-impl<DB: Database> RecordKeeperImpl<DB> {
     /// Construct a new RecordKeeper from an already opened database and possibly an existing set of
     /// rules.
     fn new(db: DB, config: RecordKeeperConfig) -> RecordKeeperImpl<DB> {
@@ -771,22 +965,289 @@ impl<DB: Database> RecordKeeperImpl<DB> {
             config: config,
             db: RwLock::new(db),
             pending_txns: RwLock::new(HashMap::new()),
+            orphans: Mutex::new(HashMap::new()),
             record_listeners: Mutex::new(ListenerPool::new()),
             game_listeners: Mutex::new(ListenerPool::new()),
+            event_codec: RwLock::new(None),
+        }
+    }
+
+    /// Add a block to the chain as `add_block_with_txns` does, but without regard for orphans.
+    fn connect_block(&self, block: &Block, txns: &HashMap<U256, (Time, Txn)>, fresh: bool) -> Result<bool, Error> {
+        let hash = block.calculate_hash();
+        if self.get_block(&hash).is_ok() {
+            return Ok(false); // block already exists
+        }
+
+        // the txns of the block which are not in the database, from those given or else pending
+        let block_txns: HashMap<U256, (Time, Txn)> = {
+            let pending = self.pending_txns.read();
+            block.txns.iter()
+                .filter_map(|h| txns.get(h).or_else(|| pending.get(h)).map(|t| (*h, t.clone())))
+                .collect()
+        };
+
+        {
+            let db = self.db.read();
+            let state = DBState::new(&*db).at(block.prev)?;
+            self.is_valid_block_given_state(&state, &block_txns, block)?;
+        }
+        self.check_finality(&*self.db.read(), &block.prev)?;
+
+        let mut pending_txns = self.pending_txns.write();
+        let mut db = self.db.write();
+
+        // get around the scope issues
+        let initial_height = db.get_current_block_height();
+        let (invalidated_blocks, earliest_invalidated_tick, uncled);
+
+        // construct a write batch of all changes we are making using a DBState object.
+        let wb = {  // because consuming state with the compile operation is not enough for the borrow checker...
+            let mut state = DBState::new(&*db);
+
+            // we know it is a valid block, so go ahead and add it and then its transactions
+            if !state.add_block(block)? {
+                // between checking if the block was valid and taking out a write lock, the block
+                // has already been added.
+                return Ok(false);
+            }
+
+            for txn_hash in block.txns.iter() {
+                if let Some(&(recv_time, ref txn)) = block_txns.get(txn_hash) { // we will need to add it
+                    pending_txns.remove(txn_hash);
+                    state.add_txn(txn, recv_time)?;
+                } else {
+                    // should already be in the DB then because otherwise is_valid_block should give an
+                    // error, so use an assert check
+                    assert!(state.get_txn(*txn_hash).is_ok())
+                }
+            }
+
+            {
+                let (a, b) = state.walk_to_head()?;
+                invalidated_blocks = a;
+                earliest_invalidated_tick = b;
+            }
+
+            uncled = hash != state.get_current_block_hash();
+
+            // couple of quick checks...
+            // if uncled, basic verification that we have not moved
+            debug_assert!(!uncled || (
+                invalidated_blocks == 0 &&
+                    initial_height == state.get_current_block_height()
+            ));
+            // if not uncled, do some validity checks to make sure we moved correctly
+            debug_assert!(uncled || (
+                initial_height > invalidated_blocks &&
+                    initial_height < state.get_current_block_height()
+            ));
+
+            // write the changes to the actual db
+            state.compile()?
+        };
+
+        let children_changed = wb.touches(&Key::from(NetworkEntry::ChildShards).as_bin());
+        db.apply(wb)?;
+
+        // pending txns may depend on the old head (e.g. block rewards), so recheck them
+        if !uncled {
+            self.expire_pending_txns(&mut *pending_txns);
+            self.revalidate_pending_txns(&*db, &mut *pending_txns);
+            if let Err(e) = self.prune(&mut *db) { warn!("Failed to prune final blocks: {}", e); }
+        }
+
+        // send out events as needed
+        let mut record_listeners = self.record_listeners.lock();
+        if invalidated_blocks > 0 {
+            record_listeners.notify(&RecordEvent::StateInvalidated {
+                new_height: db.get_current_block_height(),
+                after_height: initial_height - invalidated_blocks,
+                after_tick: earliest_invalidated_tick
+            });
+        }
+        record_listeners.notify(&RecordEvent::NewBlock { uncled, fresh, block: block.clone() });
+        if children_changed {
+            match db.get_child_shards() {
+                Ok(children) => { record_listeners.notify(&RecordEvent::ChildShards { children }); },
+                Err(e) => warn!("Could not look up child shards: {}", e)
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Add the blocks of an unpacked package and their txns to the database in one write.
+    fn import_blocks(&self, blocks: Vec<Block>, txns: HashMap<U256, (Time, Txn)>, time: Time) -> Result<(), Error> {
+        // Lock the state as we make verify and plan (and eventually make) changes
+        let mut pending_txns = self.pending_txns.write();
+        let mut db = self.db.write();
+
+        let initial_height = db.get_current_block_height();
+        let initial_block = db.get_current_block_hash();
+
+        // get around scope issues.
+        let (invalidated_blocks, earliest_invalidated_tick);
+
+        let wb = {
+            let mut state = DBState::new(&*db);
+
+            // Add all blocks and associated transactions and verify they are valid
+            for block in blocks.iter() {
+                let block_hash = block.calculate_hash();
+                if state.get_block_header(&block_hash).is_ok() {
+                    // the block has already in the system.
+                    continue;
+                }
+
+                { // Check if it is valid
+                    // Yipee for second level differences!
+                    // (I just new making state implement DB would be worthwhile...)
+                    // TODO: should we walk our own state forward to reduce how far subsequent blocks have to walk to get to the same place?
+                    let prior_block_state = DBState::new(&state).at(block.prev)?;
+                    self.is_valid_block_given_state(&prior_block_state, &txns, &block)?;
+                    self.check_finality(&state, &block.prev)?;
+                }
+
+                let added = state.add_block(&block)?;
+                debug_assert!(added); // already verified it was not present
+
+                for txn_hash in block.txns.iter() {
+                    let txn = txns.get(txn_hash)
+                        .expect("Missing transaction after validating block from block package.");
+                    state.add_txn(&txn.1, time)?;
+                }
+
+                state.walk_to_head()?;
+            }
+
+            let (_undone_block, new_blocks, earliest_tick) =
+                state.calculate_invalidations_to_block(&initial_block)?;
+            invalidated_blocks = new_blocks;
+            earliest_invalidated_tick = earliest_tick;
+
+            state.compile()?
+        };
+
+        // Write the changes
+        let children_changed = wb.touches(&Key::from(NetworkEntry::ChildShards).as_bin());
+        db.apply(wb)?;
+        if let Err(e) = self.prune(&mut *db) { warn!("Failed to prune final blocks: {}", e); }
+        let db = db.downgrade();
+
+        // Validate all pending transactions against the new state
+        self.revalidate_pending_txns(&*db, &mut *pending_txns);
+        drop(pending_txns);
+
+        // Notify listeners of the changes
+        let mut record_listeners = self.record_listeners.lock();
+
+        for block in blocks {
+            let uncled = db.is_part_of_current_chain(block.calculate_hash())?;
+            record_listeners.notify(&RecordEvent::NewBlock { uncled, fresh: false, block });
+        }
+
+        if invalidated_blocks > 0 {
+            record_listeners.notify(&RecordEvent::StateInvalidated {
+                new_height: db.get_current_block_height(),
+                after_height: initial_height - invalidated_blocks,
+                after_tick: earliest_invalidated_tick
+            });
+        }
+
+        if children_changed {
+            match db.get_child_shards() {
+                Ok(children) => { record_listeners.notify(&RecordEvent::ChildShards { children }); },
+                Err(e) => warn!("Could not look up child shards: {}", e)
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Hold on to a block whose parent is not yet known, dropping the oldest orphans if the pool
+    /// would exceed its limit.
+    fn add_orphan(&self, block: &Block, fresh: bool) {
+        let size = orphan_size(block);
+        if size > self.config.orphan_block_limit {
+            return;
+        }
+
+        let mut orphans = self.orphans.lock();
+        self.expire_orphans(&mut *orphans);
+        let mut total = orphans.values().fold(0, |acc, o| acc + orphan_size(&o.1));
+        while total + size > self.config.orphan_block_limit {
+            let oldest = *orphans.iter()
+                .min_by_key(|&(h, o)| (o.0, *h))
+                .unwrap().0;
+            let (_, dropped, _) = orphans.remove(&oldest).unwrap();
+            debug!("Dropping orphan block ({}) to make room", oldest);
+            total -= orphan_size(&dropped);
+        }
+
+        debug!("Holding orphan block ({}) until its parent ({}) arrives", block.calculate_hash(), block.prev);
+        orphans.insert(block.calculate_hash(), (Time::current(), block.clone(), fresh));
+    }
+
+    /// Add any orphans which were waiting on `parent`, and then any which were waiting on those.
+    fn connect_orphans(&self, parent: U256) {
+        let mut parents = vec![parent];
+        while let Some(parent) = parents.pop() {
+            let children: Vec<(Time, Block, bool)> = {
+                let mut orphans = self.orphans.lock();
+                self.expire_orphans(&mut *orphans);
+                let hashes: Vec<U256> = orphans.iter()
+                    .filter(|&(_, o)| o.1.prev == parent)
+                    .map(|(h, _)| *h)
+                    .collect();
+                hashes.iter().filter_map(|h| orphans.remove(h)).collect()
+            };
+
+            for (_, block, fresh) in children {
+                let hash = block.calculate_hash();
+                match self.connect_block(&block, &HashMap::new(), fresh) {
+                    Ok(_) => parents.push(hash),
+                    Err(e) => debug!("Could not connect orphan block ({}): {}", hash, e)
+                }
+            }
+        }
+    }
+
+    /// Drop any orphans which have waited longer than the TTL for their parent.
+    fn expire_orphans(&self, orphans: &mut HashMap<U256, (Time, Block, bool)>) {
+        let cutoff = Time::from_milliseconds(Time::current().millis() - (self.config.orphan_block_ttl as i64) * 1000);
+        let before = orphans.len();
+        orphans.retain(|_, o| o.0 >= cutoff);
+        if orphans.len() < before {
+            debug!("Expired {} orphan blocks.", before - orphans.len());
         }
     }
 
     /// Internal use function to check if a block and all its sub-components are valid.
     fn is_valid_block_given_state(&self, prev_block_state: &DBState, pending: &HashMap<U256, (Time, Txn)>, block: &Block) -> Result<(), Error> {
-        rules::block::TimeStamp.is_valid(prev_block_state, block)?;
-        rules::block::MerkleRoot.is_valid(prev_block_state, block)?;
+        // base rules
+        rules::block::BlockSize {
+            max_bytes: self.config.max_block_size,
+            max_txns: self.config.max_block_txns
+        }.is_valid(prev_block_state, block)?;
+
+        // user-added rules
+        for rule in &self.config.block_rules {
+            rule.is_valid(prev_block_state, block)?;
+        }
 
         let mut mutation = Vec::new();
+        let mut size = BlockPackage::header_size(block) as u64;
         for txn_hash in &block.txns {
             let txn = pending.get(txn_hash)
                 .map(|t| Ok(t.1.clone()))
                 .unwrap_or_else(|| prev_block_state.get_txn(*txn_hash))?;
 
+            size += txn.calculate_size() as u64;
+            if size > self.config.max_block_size {
+                return Err(LogicError::BlockTooLarge.into());
+            }
+
             self.is_valid_txn_given_state(prev_block_state, &txn)?;
             for change in txn.mutation.changes {
                 mutation.push((change, txn.creator));
@@ -817,12 +1278,86 @@ impl<DB: Database> RecordKeeperImpl<DB> {
         self.is_valid_mutation_given_state(&state, &mutation)
     }
 
+    /// Make sure a block building on `prev` would not require reorging any final blocks. This should
+    /// only be called once the block is known to be valid.
+    fn check_finality(&self, db: &Database, prev: &U256) -> Result<(), Error> {
+        let current = db.get_current_block_hash();
+        if *prev == current { return Ok(()); }
+
+        // anything below the finality depth cannot be built on, so do not bother finding the path
+        if db.get_block_height(*prev)? + self.config.finality_depth < db.get_current_block_height() {
+            return Err(LogicError::BeyondFinality.into());
+        }
+
+        let (undone, _) = db.calculate_block_path(&current, prev)?;
+        if undone.len() as u64 > self.config.finality_depth {
+            Err(LogicError::BeyondFinality.into())
+        } else { Ok(()) }
+    }
+
+    /// When running in `Light` mode, remove the undo information for blocks which have become final
+    /// since they can no longer be reorged.
+    fn prune(&self, db: &mut DB) -> Result<(), Error> {
+        match self.config.index_strategy {
+            RecordKeeperIndexingStrategy::Light => {},
+            _ => return Ok(())
+        }
+
+        let height = db.get_current_block_height();
+        if height <= self.config.finality_depth { return Ok(()); }
+
+        // work down until we find blocks which were already pruned (genesis has no contra)
+        let mut h = height - self.config.finality_depth;
+        while h > 1 {
+            let main = db.get_current_block_of_height(h)?;
+            let mut pruned = false;
+            for hash in db.get_blocks_of_height(h)? {
+                pruned |= db.prune_final_block(hash)? && hash == main;
+            }
+
+            if !pruned { break; }
+            h -= 1;
+        }
+
+        Ok(())
+    }
+
+    /// Drop any pending txns which are no longer valid given the current state of the database.
+    /// Txns are rechecked oldest first so those which depend on earlier ones are kept.
+    fn revalidate_pending_txns(&self, db: &Database, pending: &mut HashMap<U256, (Time, Txn)>) {
+        use std::mem::swap;
+
+        let mut txns = HashMap::with_capacity(pending.len());
+        swap(&mut txns, pending);
+
+        let mut ordered: Vec<(U256, (Time, Txn))> = txns.into_iter().collect();
+        ordered.sort_by_key(|&(hash, (time, _))| (time, hash));
+
+        for (txn_hash, (recv_time, txn)) in ordered {
+            if self.is_valid_txn_given_lock(db, &*pending, &txn).is_err() {
+                continue;
+            }
+            // else
+            pending.insert(txn_hash, (recv_time, txn));
+        }
+    }
+
+    /// Drop any pending txns which have been waiting longer than the configured TTL.
+    fn expire_pending_txns(&self, pending: &mut HashMap<U256, (Time, Txn)>) {
+        let cutoff = Time::from_milliseconds(Time::current().millis() - (self.config.pending_txn_ttl as i64) * 1000);
+        let before = pending.len();
+        pending.retain(|_, &mut (time, _)| time >= cutoff);
+        if pending.len() < before {
+            debug!("Expired {} pending txns.", before - pending.len());
+        }
+    }
+
     /// Internal use function, check if a txn is valid.
     fn is_valid_txn_given_state(&self, state: &DBState, txn: &Txn) -> Result<(), Error> {
-        rules::txn::Signature.is_valid(state, txn)?;
-        rules::txn::AdminCheck.is_valid(state, txn)?;
-        rules::txn::NewValidator.is_valid(state, txn)?;
-        rules::txn::Duplicates.is_valid(state, txn)
+        for rule in &self.config.txn_rules {
+            rule.is_valid(state, txn)?;
+        }
+        Ok(())
     }
 
     /// Internal use function to check if a mutation is valid.
@@ -831,6 +1366,12 @@ impl<DB: Database> RecordKeeperImpl<DB> {
         // base rules
         rules::mutation::PlotEvent.is_valid(prev_block_state, mutation, &mut cache)?;
         rules::mutation::Duplicates.is_valid(prev_block_state, mutation, &mut cache)?;
+        rules::mutation::Shares.is_valid(prev_block_state, mutation, &mut cache)?;
+        rules::mutation::ShardSplits.is_valid(prev_block_state, mutation, &mut cache)?;
+        rules::mutation::ChildBlockRefs::new(self.config.shard_headers.clone()).is_valid(prev_block_state, mutation, &mut cache)?;
+        rules::mutation::ShardTransfers::new(self.config.shard_headers.clone(), self.config.finality_depth).is_valid(prev_block_state, mutation, &mut cache)?;
+        SlashEvidence::new(self.config.validators_scan).is_valid(prev_block_state, mutation, &mut cache)?;
+        EPoSRewards.is_valid(prev_block_state, mutation, &mut cache)?;
 
         // user-added rules
         cache = Bin::new();
@@ -842,3 +1383,406 @@ impl<DB: Database> RecordKeeperImpl<DB> {
         Ok(())
     }
 }
+
+
+/// List the pending txns in the order they should be included in a block: oldest first, with ties
+/// broken by hash.
+fn pending_order(pending: &HashMap<U256, (Time, Txn)>) -> Vec<(U256, Time, &Txn)> {
+    let mut txns: Vec<_> = pending.iter()
+        .map(|(hash, &(time, ref txn))| (*hash, time, txn))
+        .collect();
+    txns.sort_by_key(|&(hash, time, _)| (time, hash));
+    txns
+}
+
+/// Check if a txn only rewards or slashes validators, which forgers make for the blocks they sign.
+fn is_forged_txn(txn: &Txn) -> bool {
+    !txn.mutation.changes.is_empty() && txn.mutation.changes.iter().all(|change| match change {
+        &Change::BlockReward { .. } | &Change::Slash { .. } => true,
+        _ => false
+    })
+}
+
+/// Approximate memory used by an orphan block.
+fn orphan_size(block: &Block) -> u64 {
+    (BlockPackage::header_size(block) + block.txns.len() * 32) as u64
+}
+
+
+/// Create an in-memory record keeper whose genesis block registers `key` as a validator with the
+/// stake of one block reward. Pending txns and orphan blocks are both kept for `ttl` seconds. This
+/// is shared with the tests of the rules which live outside of record keeper.
+#[cfg(test)]
+pub fn test_record_keeper(key: &PKey, limit: u64, per_creator: usize, ttl: u64) -> RecordKeeperImpl<MemoryDatabase> {
+    test_indexed_record_keeper(key, RecordKeeperIndexingStrategy::Full, limit, per_creator, ttl)
+}
+
+/// Create an in-memory record keeper as `test_record_keeper` does, using the given indexing
+/// strategy.
+#[cfg(test)]
+pub fn test_indexed_record_keeper(key: &PKey, strategy: RecordKeeperIndexingStrategy, limit: u64, per_creator: usize, ttl: u64) -> RecordKeeperImpl<MemoryDatabase> {
+    use hash::hash_pub_key;
+    use super::default_txn_rules;
+
+    let pub_key: Bin = key.public_key_to_der().unwrap().into();
+    let config = RecordKeeperConfig {
+        pending_txn_limit: limit,
+        pending_txns_per_creator: per_creator,
+        pending_txn_ttl: ttl,
+        orphan_block_limit: 1 << 20,
+        orphan_block_ttl: ttl,
+        max_block_size: 1 << 20,
+        max_block_txns: 1000,
+        index_strategy: strategy,
+        finality_depth: 100,
+        validators_scan: 100,
+        block_rules: BlockRules::new(),
+        txn_rules: default_txn_rules(),
+        rules: MutationRules::new(),
+        shard_headers: None,
+        genesis_stake: vec![(hash_pub_key(&pub_key), BLOCK_REWARD)]
+    };
+
+    let mut mutation = Mutation::new();
+    mutation.changes.push(Change::NewValidator { pub_key: pub_key.clone() });
+    // a fixed timestamp so record keepers made with the same key share a genesis block
+    let txn = Txn {
+        timestamp: Time::from_milliseconds(0),
+        creator: hash_pub_key(&pub_key),
+        mutation,
+        signature: Bin::new()
+    }.sign(key);
+
+    let txns = vec![txn.calculate_hash()];
+    let genesis = Block {
+        header: BlockHeader {
+            version: 1,
+            timestamp: Time::from_milliseconds(0),
+            shard: U256_ZERO,
+            prev: U256_ZERO,
+            merkle_root: Block::calculate_merkle_root(&txns),
+            blob: Bin::new()
+        },
+        txns
+    };
+
+    RecordKeeperImpl::in_memory(config, (genesis, vec![txn])).unwrap()
+}
+
+/// Create a signed txn with a single plot event at the given tick.
+#[cfg(test)]
+fn test_event_txn(key: &PKey, tick: u64) -> Txn {
+    use std::collections::BTreeSet;
+    use hash::hash_pub_key;
+    use primitives::Coord;
+
+    let mut mutation = Mutation::new();
+    mutation.changes.push(Change::PlotEvent(PlotEvent { from: Coord(0, 0), to: BTreeSet::new(), tick, event: Bin::new() }));
+    Txn::new(hash_pub_key(&key.public_key_to_der().unwrap()), mutation).sign(key)
+}
+
+#[test]
+fn pending_txn_limits() {
+    use std::thread::sleep;
+    use std::time::Duration;
+    use signer::generate_private_key;
+
+    let key = generate_private_key();
+    let is_pending = |rk: &RecordKeeperImpl<MemoryDatabase>, txn: &Txn| rk.get_txn_blocks(txn.calculate_hash()).ok() == Some(None);
+
+    // txns older than the TTL are dropped as new ones come in
+    let rk = test_record_keeper(&key, 1 << 20, 16, 0);
+    let (a, b) = (test_event_txn(&key, 1), test_event_txn(&key, 2));
+    assert!(rk.add_pending_txn(a.clone(), true).unwrap());
+    sleep(Duration::from_millis(5));
+    assert!(rk.add_pending_txn(b.clone(), true).unwrap());
+    assert!(!is_pending(&rk, &a));
+    assert!(is_pending(&rk, &b));
+
+    // a creator may only have so many txns pending
+    let rk = test_record_keeper(&key, 1 << 20, 2, 3600);
+    assert!(rk.add_pending_txn(test_event_txn(&key, 1), true).unwrap());
+    assert!(rk.add_pending_txn(test_event_txn(&key, 2), true).unwrap());
+    match rk.add_pending_txn(test_event_txn(&key, 3), true) {
+        Err(Error::OutOfMemory(_)) => (),
+        r => panic!("Expected the creator quota to be enforced, got {:?}", r)
+    }
+
+    // when the pool is full, new txns are refused rather than pushing out older ones
+    let (a, b, c) = (test_event_txn(&key, 1), test_event_txn(&key, 2), test_event_txn(&key, 3));
+    let size = a.calculate_size() as u64;
+    let rk = test_record_keeper(&key, size * 2 + size / 2, 16, 3600);
+    assert!(rk.add_pending_txn(a.clone(), true).unwrap());
+    assert!(rk.add_pending_txn(b.clone(), true).unwrap());
+    match rk.add_pending_txn(c.clone(), true) {
+        Err(Error::OutOfMemory(_)) => (),
+        r => panic!("Expected the full pool to refuse the txn, got {:?}", r)
+    }
+    assert!(is_pending(&rk, &a));
+    assert!(is_pending(&rk, &b));
+    assert!(!is_pending(&rk, &c));
+}
+
+#[test]
+fn snapshot_export_and_import() {
+    use primitives::Coord;
+    use signer::generate_private_key;
+
+    let key = generate_private_key();
+    let mut source = test_record_keeper(&key, 1 << 20, 16, 3600);
+    source.config.finality_depth = 1;
+    let txns: Vec<Txn> = (1..4).map(|tick| test_event_txn(&key, tick)).collect();
+    let mut blocks = Vec::new();
+    for txn in txns.iter() {
+        assert!(source.add_pending_txn(txn.clone(), true).unwrap());
+        let block = source.create_block().unwrap();
+        assert!(source.add_block(&block, true).unwrap());
+        blocks.push(block);
+    }
+
+    // the head (height 4) is not final yet
+    match source.export_snapshot(4, 1, &key) {
+        Err(Error::Logic(LogicError::InvalidSnapshot(_))) => (),
+        r => panic!("Expected only final blocks to be snapshotted, got {:?}", r.map(|s| s.height))
+    }
+
+    // the snapshot holds the state as of its height rather than the head
+    let snapshot = Snapshot::decode(&source.export_snapshot(3, 1, &key).unwrap().encode()).unwrap();
+    let dest = test_record_keeper(&key, 1 << 20, 16, 3600);
+    assert_eq!(dest.import_snapshot(snapshot, &key).unwrap(), blocks[1].calculate_hash());
+    assert_eq!(dest.get_plot_events(Coord(0, 0), 0).unwrap().len(), 2);
+
+    // and syncing carries on from there
+    assert!(dest.add_block_with_txns(&blocks[2], vec![txns[2].clone()], true).unwrap());
+    assert_eq!(dest.get_current_block_hash(), blocks[2].calculate_hash());
+    assert_eq!(dest.get_plot_events(Coord(0, 0), 0).unwrap().len(), 3);
+
+    match dest.import_snapshot(source.export_snapshot(3, 1, &key).unwrap(), &key) {
+        Err(Error::Logic(LogicError::AlreadySynced)) => (),
+        r => panic!("Expected a synced record keeper to refuse a snapshot, got {:?}", r)
+    }
+}
+
+#[test]
+fn standard_indexing_leaves_out_account_txns() {
+    use hash::hash_pub_key;
+    use signer::generate_private_key;
+
+    let key = generate_private_key();
+    let account = hash_pub_key(&key.public_key_to_der().unwrap());
+    let build = |strategy| {
+        let rk = test_indexed_record_keeper(&key, strategy, 1 << 20, 16, 3600);
+        let txn = test_event_txn(&key, 1);
+        assert!(rk.add_pending_txn(txn.clone(), true).unwrap());
+        let block = rk.create_block().unwrap();
+        assert!(rk.add_block(&block, true).unwrap());
+        (rk, txn.calculate_hash(), block.calculate_hash())
+    };
+
+    let (rk, txn, _) = build(RecordKeeperIndexingStrategy::Full);
+    assert!(rk.get_account_txns(&account).unwrap().contains(&txn));
+    assert!(rk.get_txn_receive_time(txn).is_ok());
+
+    // the blocks of a txn are still known, but not who made it or when it arrived
+    let (rk, txn, block) = build(RecordKeeperIndexingStrategy::Standard);
+    assert_eq!(rk.get_txn_blocks(txn).unwrap(), Some(vec![block].into_iter().collect()));
+    match rk.get_account_txns(&account) {
+        Err(Error::IndexDisabled(_)) => (),
+        r => panic!("Expected account txns to not be indexed, got {:?}", r)
+    }
+    match rk.get_txn_receive_time(txn) {
+        Err(Error::IndexDisabled(_)) => (),
+        r => panic!("Expected receive times to not be indexed, got {:?}", r)
+    }
+}
+
+#[test]
+fn light_indexing_prunes_final_blocks() {
+    use signer::generate_private_key;
+
+    let key = generate_private_key();
+    let mut rk = test_indexed_record_keeper(&key, RecordKeeperIndexingStrategy::Light, 1 << 20, 16, 3600);
+    rk.config.finality_depth = 1;
+    let mut blocks = Vec::new();
+    for tick in 1..4 {
+        assert!(rk.add_pending_txn(test_event_txn(&key, tick), true).unwrap());
+        let block = rk.create_block().unwrap();
+        assert!(rk.add_block(&block, true).unwrap());
+        blocks.push(block);
+    }
+
+    // only the head can still be reorged, so only it keeps its undo information and txns
+    let db = rk.db.read();
+    for block in blocks[..2].iter() {
+        assert!(db._get_contra(block.calculate_hash()).is_err());
+        assert!(db.get_txn(block.txns[0]).is_err());
+    }
+    assert!(db._get_contra(blocks[2].calculate_hash()).is_ok());
+    assert!(db.get_txn(blocks[2].txns[0]).is_ok());
+}
+
+#[test]
+fn pending_plot_events() {
+    use std::collections::BTreeSet;
+    use hash::hash_pub_key;
+    use primitives::Coord;
+    use signer::generate_private_key;
+
+    let key = generate_private_key();
+    let rk = test_record_keeper(&key, 1 << 20, 16, 3600);
+    let mut mutation = Mutation::new();
+    let to: BTreeSet<_> = vec![Coord(1, 0)].into_iter().collect();
+    mutation.changes.push(Change::PlotEvent(PlotEvent { from: Coord(0, 0), to, tick: 3, event: Bin::new() }));
+    assert!(rk.add_pending_txn(Txn::new(hash_pub_key(&key.public_key_to_der().unwrap()), mutation).sign(&key), true).unwrap());
+
+    // pending events reach the plots they are sent to, but only from the requested tick
+    for plot in vec![Coord(0, 0), Coord(1, 0)] {
+        assert_eq!(rk.get_plot_events(plot, 3).unwrap().len(), 1);
+        assert!(rk.get_plot_events(plot, 4).unwrap().is_empty());
+    }
+    assert!(rk.get_plot_events(Coord(2, 0), 0).unwrap().is_empty());
+}
+
+#[test]
+fn account_txn_pages() {
+    use hash::hash_pub_key;
+    use signer::generate_private_key;
+
+    let key = generate_private_key();
+    let account = hash_pub_key(&key.public_key_to_der().unwrap());
+    let rk = test_record_keeper(&key, 1 << 20, 16, 3600);
+    let genesis = rk.get_block(&rk.get_current_block_hash()).unwrap();
+
+    // two txns in the first block and one in the second
+    let mut blocks = Vec::new();
+    for ticks in vec![vec![1, 2], vec![3]] {
+        for tick in ticks {
+            assert!(rk.add_pending_txn(test_event_txn(&key, tick), true).unwrap());
+        }
+        let block = rk.create_block().unwrap();
+        assert!(rk.add_block(&block, true).unwrap());
+        blocks.push(block);
+    }
+    let (first, second) = (&blocks[0], &blocks[1]);
+
+    let page = rk.get_account_txns_before(&account, None, 2).unwrap();
+    assert_eq!(page, vec![(3, 0, second.txns[0]), (2, 1, first.txns[1])]);
+    let page = rk.get_account_txns_before(&account, Some((2, 1)), 2).unwrap();
+    assert_eq!(page, vec![(2, 0, first.txns[0]), (1, 0, genesis.txns[0])]);
+    assert!(rk.get_account_txns_before(&account, Some((1, 0)), 2).unwrap().is_empty());
+
+    // a position beyond the head is not a fresh start
+    assert!(rk.get_account_txns_before(&account, Some((4, 0)), 2).unwrap().is_empty());
+
+    // block ranges are clamped to the chain
+    assert_eq!(rk.get_blocks_range(0, 10).unwrap(), vec![genesis.header.clone(), first.header.clone(), second.header.clone()]);
+    assert_eq!(rk.get_blocks_range(2, 2).unwrap(), vec![first.header.clone()]);
+    assert_eq!(rk.get_blocks_range(3, u64::max_value()).unwrap(), vec![second.header.clone()]);
+}
+
+#[test]
+fn orphans_connect_to_parent() {
+    use std::thread::sleep;
+    use std::time::Duration;
+    use signer::generate_private_key;
+
+    let key = generate_private_key();
+    let make_blocks = |rk: &RecordKeeperImpl<MemoryDatabase>| {
+        let parent = rk.create_block().unwrap();
+        let mut child = parent.clone();
+        child.prev = parent.calculate_hash();
+        child.timestamp = Time::from_milliseconds(parent.timestamp.millis() + 1);
+        (parent, child)
+    };
+
+    // the child is held until the parent arrives and then connected along with it
+    let rk = test_record_keeper(&key, 1 << 20, 16, 3600);
+    let (parent, child) = make_blocks(&rk);
+    match rk.add_block(&child, true) {
+        Err(Error::NotFound(..)) => (),
+        r => panic!("Expected the parent to be missing, got {:?}", r)
+    }
+    assert!(rk.add_block(&parent, true).unwrap());
+    assert_eq!(rk.get_current_block_hash(), child.calculate_hash());
+
+    // orphans older than the TTL are dropped instead
+    let rk = test_record_keeper(&key, 1 << 20, 16, 0);
+    let (parent, child) = make_blocks(&rk);
+    assert!(rk.add_block(&child, true).is_err());
+    sleep(Duration::from_millis(5));
+    assert!(rk.add_block(&parent, true).unwrap());
+    assert_eq!(rk.get_current_block_hash(), parent.calculate_hash());
+}
+
+#[test]
+fn shard_transfer_and_claim() {
+    use std::collections::BTreeSet;
+    use hash::hash_pub_key;
+    use network::ShardHeaderIndex;
+    use primitives::{Coord, ShardTransferProof};
+    use signer::generate_private_key;
+
+    let (source_key, dest_key) = (generate_private_key(), generate_private_key());
+    let source = test_record_keeper(&source_key, 1 << 20, 16, 3600);
+    let mut dest = test_record_keeper(&dest_key, 1 << 20, 16, 3600);
+    let (source_id, dest_id) = (source.get_current_block_hash(), dest.get_current_block_hash());
+    let sender = hash_pub_key(&source_key.public_key_to_der().unwrap());
+    let recipient = hash_pub_key(&dest_key.public_key_to_der().unwrap());
+
+    // the destination follows the headers of the source shard
+    let index = Arc::new(ShardHeaderIndex::new());
+    let chain = index.attach(source_id, source.get_current_block_header().unwrap());
+    dest.config.shard_headers = Some(index.clone());
+    dest.config.finality_depth = 2;
+
+    // lock some shares and an event on the source shard
+    let id = U256::from(1);
+    let event = PlotEvent { from: Coord(0, 0), to: BTreeSet::new(), tick: 1, event: Bin::new() };
+    let mut mutation = Mutation::new();
+    mutation.changes.push(Change::ShardTransfer {
+        id, from: sender, to_shard: dest_id,
+        to: vec![(recipient, 4)].into_iter().collect(),
+        events: vec![event]
+    });
+    let transfer = Txn::new(sender, mutation).sign(&source_key);
+    assert!(source.add_pending_txn(transfer.clone(), true).unwrap());
+    let block = source.create_block().unwrap();
+    assert!(source.add_block(&block, true).unwrap());
+    assert!(chain.write().add_header(block.header.clone(), None).unwrap());
+    assert_eq!(source.get_validator_stake(&sender).unwrap(), BLOCK_REWARD - 4);
+
+    let proof = ShardTransferProof {
+        header: block.header.clone(),
+        branch: block.merkle_proof(&transfer.calculate_hash()).unwrap(),
+        txn: transfer
+    };
+    let mut mutation = Mutation::new();
+    mutation.changes.push(Change::ShardClaim { id, from_shard: source_id, proof: proof.encode() });
+    let claim = Txn::new(recipient, mutation.clone()).sign(&dest_key);
+
+    // the transfer cannot be claimed until it is final on the source shard
+    match dest.add_pending_txn(claim.clone(), true) {
+        Err(Error::Logic(LogicError::InvalidMutation(_))) => (),
+        r => panic!("Expected the claim to wait for finality, got {:?}", r)
+    }
+    for _ in 0..2 {
+        let next = source.create_block().unwrap();
+        assert!(source.add_block(&next, true).unwrap());
+        assert!(chain.write().add_header(next.header.clone(), None).unwrap());
+    }
+
+    // once claimed, the shares and the event arrive on the destination
+    assert_eq!((claim.mutation.earliest_game_event(), claim.mutation.latest_game_event()), (1, 1));
+    assert!(dest.add_pending_txn(claim, true).unwrap());
+    let block = dest.create_block().unwrap();
+    assert!(dest.add_block(&block, true).unwrap());
+    assert_eq!(dest.get_validator_stake(&recipient).unwrap(), BLOCK_REWARD + 4);
+    assert_eq!(dest.get_plot_events(Coord(0, 0), 0).unwrap().get(&1), Some(&vec![Bin::new()]));
+
+    // and it can only be claimed once
+    mutation.changes.push(Change::PlotEvent(PlotEvent { from: Coord(0, 1), to: BTreeSet::new(), tick: 2, event: Bin::new() }));
+    match dest.add_pending_txn(Txn::new(recipient, mutation).sign(&dest_key), true) {
+        Err(Error::Logic(LogicError::Duplicate)) => (),
+        r => panic!("Expected a second claim to be rejected, got {:?}", r)
+    }
+}
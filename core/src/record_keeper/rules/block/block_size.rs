@@ -0,0 +1,23 @@
+use primitives::Block;
+use record_keeper::{Error, LogicError, DBState, BlockPackage};
+use record_keeper::rules::BlockRule;
+
+/// The block must not have more txns than allowed, and its header and txn list must fit within the
+/// maximum block size. The txns themselves may not be known yet, so their size is checked when
+/// they are validated together with the block.
+pub struct BlockSize {
+    pub max_bytes: u64,
+    pub max_txns: u64
+}
+
+impl BlockRule for BlockSize {
+    fn is_valid(&self, _prev_state: &DBState, block: &Block) -> Result<(), Error> {
+        if (block.txns.len() as u64) > self.max_txns ||
+           (BlockPackage::header_size(block) as u64) > self.max_bytes
+        { Err(LogicError::BlockTooLarge.into()) } else { Ok(()) }
+    }
+
+    fn description(&self) -> &'static str {
+        "The block must not exceed the maximum size or number of txns."
+    }
+}
@@ -1,5 +1,7 @@
+mod block_size;
 mod merkle_root;
 mod time_stamp;
 
+pub use self::block_size::*;
 pub use self::merkle_root::*;
 pub use self::time_stamp::*;
\ No newline at end of file
@@ -53,7 +53,7 @@ pub trait MutationRule: Send + Sync {
     fn description(&self) -> &'static str;
 }
 
-/// Simplify iterating over PlotEvents for Mutation Rules.
+/// Simplify iterating over PlotEvents for Mutation Rules, including those delivered by claims.
 pub fn plot_events_rule_iter<F>(mut func: F, mutation: &Vec<(Change, U160)>) -> Result<(), Error>
     where F: FnMut(&PlotEvent, U160) -> Result<(), Error>
 {
@@ -61,10 +61,13 @@ pub fn plot_events_rule_iter<F>(mut func: F, mutation: &Vec<(Change, U160)>) ->
         if let &Change::PlotEvent(ref pe) = change {
             func(pe, *user)?;
         }
+        for pe in change.claimed_events() {
+            func(&pe, *user)?;
+        }
     } Ok(())
 }
 
-/// Simply iterating over game events for Mutation Rules.
+/// Simply iterating over game events for Mutation Rules, including those delivered by claims.
 pub fn game_events_rule_iter<F, E>(mut func: F, mutation: &Vec<(Change, U160)>) -> Result<(), Error>
     where F: FnMut(E, U160) -> Result<(), Error>,
           E: Event + DeserializeOwned
@@ -73,13 +76,42 @@ pub fn game_events_rule_iter<F, E>(mut func: F, mutation: &Vec<(Change, U160)>)
         if let &Change::PlotEvent(ref pe) = change {
             func(bincode::deserialize(&pe.event)?, *user)?;
         }
+        for pe in change.claimed_events() {
+            func(bincode::deserialize(&pe.event)?, *user)?;
+        }
     } Ok(())
 }
 
+/// A list of block rules
+pub type BlockRules = LinkedList<Box<BlockRule>>;
+
+/// A list of txn rules
+pub type TxnRules = LinkedList<Box<TxnRule>>;
+
 /// A list of mutation rules
 pub type MutationRules = LinkedList<Box<MutationRule>>;
 
 
+/// Construct the standard set of block rules which all networks are expected to enforce. Block size
+/// limits come from the record keeper config and are always enforced, so they are not included.
+pub fn default_block_rules() -> BlockRules {
+    let mut rules = BlockRules::new();
+    rules.push_back(Box::new(block::TimeStamp));
+    rules.push_back(Box::new(block::MerkleRoot));
+    rules
+}
+
+/// Construct the standard set of txn rules which all networks are expected to enforce.
+pub fn default_txn_rules() -> TxnRules {
+    let mut rules = TxnRules::new();
+    rules.push_back(Box::new(txn::Signature));
+    rules.push_back(Box::new(txn::AdminCheck));
+    rules.push_back(Box::new(txn::NewValidator));
+    rules.push_back(Box::new(txn::Duplicates));
+    rules
+}
+
+
 
 // Wrap all of the rules with Debug trait which simply calls their description function.
 impl Debug for BlockRule {
@@ -0,0 +1,69 @@
+use bin::Bin;
+use primitives::{Change, U160, U256};
+use record_keeper::{Error, LogicError, DBState, Database};
+use record_keeper::rules::MutationRule;
+use record_keeper::rules::mutation::ShardHeaders;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// References to child shard blocks must be made by a validator, be for a known child shard, and
+/// always move forward; a reference may not repeat or go below the last anchored height.
+///
+/// The referenced block must be on the header chain of the child as this node follows it, which
+/// only holds headers that passed the header verifier, and the height must be its height on that
+/// chain. This way a reference cannot jump ahead of what the child has actually produced.
+pub struct ChildBlockRefs {
+    /// Source of the headers of the child shards; without it no references can be verified
+    pub headers: Option<Arc<ShardHeaders>>
+}
+
+impl ChildBlockRefs {
+    pub fn new(headers: Option<Arc<ShardHeaders>>) -> ChildBlockRefs {
+        ChildBlockRefs { headers }
+    }
+}
+
+impl MutationRule for ChildBlockRefs {
+    fn is_valid(&self, state: &DBState, mutation: &Vec<(Change, U160)>, _cache: &mut Bin) -> Result<(), Error> {
+        let mut children: Option<Vec<U256>> = None;
+        let mut anchored: HashMap<U256, u64> = HashMap::new();
+
+        for &(ref change, creator) in mutation {
+            if let &Change::ChildBlockRef { shard, block, height } = change {
+                if state.get_validator_key(creator).is_err() {
+                    return Err(LogicError::UnrecognizedCreator.into());
+                }
+
+                if children.is_none() {
+                    children = Some(state.get_child_shards()?.into_iter().map(|c| c.0).collect());
+                }
+                if !children.as_ref().unwrap().contains(&shard) {
+                    return Err(LogicError::InvalidMutation("Block reference is for an unknown child shard".into()).into());
+                }
+
+                let headers = self.headers.as_ref()
+                    .ok_or_else(|| LogicError::InvalidMutation("Cannot verify block references without the headers of child shards".into()))?;
+                if headers.get_height(&shard, &block) != Some(height) {
+                    return Err(LogicError::InvalidMutation("Block reference is not for a known header of the child shard".into()).into());
+                }
+
+                let last = match anchored.get(&shard) {
+                    Some(&h) => Some(h),
+                    None => state.get_child_block_ref(&shard)?.map(|r| r.1)
+                };
+
+                if last.map_or(false, |h| height <= h) {
+                    return Err(LogicError::InvalidMutation("Block references must move forward".into()).into());
+                }
+
+                anchored.insert(shard, height);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn description(&self) -> &'static str {
+        "Child block references must be for a verified header of a known child shard and must always move forward."
+    }
+}
@@ -1,14 +1,14 @@
 use bin::Bin;
 use hash::hash_pub_key;
 use primitives::{Change, RawEvent, RawEvents, U160};
-use record_keeper::{Error, LogicError, DBState, PlotID, Database};
+use record_keeper::{Error, LogicError, DBState, PlotEvent, PlotID, Database};
 use primitives::add_event;
 use record_keeper::rules::MutationRule;
 use std::collections::{HashSet, HashMap};
 
 
 /// Make sure there are not duplicate changes in a mutation which are clearly invalid, such as
-/// duplicate NewValidator or duplicate PlotEvents
+/// duplicate NewValidator or duplicate PlotEvents (including those delivered by a ShardClaim)
 pub struct Duplicates;
 impl Duplicates {
     /// Check if a given event is already in the set of events at the given tick.
@@ -33,6 +33,35 @@ impl Duplicates {
         } Ok(false)
     }
 
+    /// Make sure an event has not been seen before in the mutation or the network state, and then
+    /// record it as seen.
+    fn check_event(state: &DBState, events: &mut HashMap<PlotID, RawEvents>, e: &PlotEvent) -> Result<(), Error> {
+        // Check if we have already encountered it in this txn
+        if Self::duplicated_within_txn(events, e.from, e.tick, &e.event) ||
+           e.to.iter()
+            .find(|&&id| Self::duplicated_within_txn(events, id, e.tick, &e.event))
+            .is_some()
+        {
+            return Err(LogicError::Duplicate.into());
+        }
+
+        // Check if it is in the network state
+        if Self::duplicated_within_net(state, e.from, e.tick, &e.event)? {
+            return Err(LogicError::Duplicate.into());
+        } for id in e.to.iter() {
+            if Self::duplicated_within_net(state, *id, e.tick, &e.event)? {
+                return Err(LogicError::Duplicate.into());
+            }
+        }
+
+        // Add it to what we have seen
+        Self::append_to_plot(events, e.from, e.tick, e.event.clone());
+        for id in e.to.iter() {
+            Self::append_to_plot(events, *id, e.tick, e.event.clone());
+        }
+        Ok(())
+    }
+
     /// Put the event into the records as having been seen for a specified plot.
     fn append_to_plot(events: &mut HashMap<PlotID, RawEvents>, id: PlotID, tick: u64, event: RawEvent) {
         if let Some(plot_events) = events.get_mut(&id) {
@@ -62,31 +91,10 @@ impl MutationRule for Duplicates {
 
                 validators.insert(hash);
             },
-            &Change::PlotEvent(ref e) => {
-                // Check if we have already encountered it in this txn
-                if Self::duplicated_within_txn(&events, e.from, e.tick, &e.event) ||
-                   e.to.iter()
-                    .find(|&&id| Self::duplicated_within_txn(&events, id, e.tick, &e.event))
-                    .is_some()
-                {
-                    return Err(LogicError::Duplicate.into());
-                }
-
-                // Check if it is in the network state
-                if Self::duplicated_within_net(state, e.from, e.tick, &e.event)? {
-                    return Err(LogicError::Duplicate.into());
-                } for id in e.to.iter() {
-                    if Self::duplicated_within_net(state, *id, e.tick, &e.event)? {
-                        return Err(LogicError::Duplicate.into());
-                    }
-                }
-                
-                // Add it to what we have seen
-                Self::append_to_plot(&mut events, e.from, e.tick, e.event.clone());
-                for id in e.to.iter() {
-                    Self::append_to_plot(&mut events, *id, e.tick, e.event.clone());
-                }
-            }
+            &Change::PlotEvent(ref e) => Self::check_event(state, &mut events, e)?,
+            &Change::ShardClaim { .. } => for e in change.claimed_events() {
+                Self::check_event(state, &mut events, &e)?;
+            },
             _ => ()
         }}
 
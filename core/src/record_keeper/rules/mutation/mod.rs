@@ -1,7 +1,13 @@
+mod child_block_ref;
 mod duplicates;
 mod plot_event;
 mod shares;
+mod shard_split;
+mod shard_transfer;
 
+pub use self::child_block_ref::*;
 pub use self::duplicates::*;
 pub use self::plot_event::*;
-pub use self::shares::*;
\ No newline at end of file
+pub use self::shares::*;
+pub use self::shard_split::*;
+pub use self::shard_transfer::*;
\ No newline at end of file
@@ -0,0 +1,81 @@
+use bin::Bin;
+use primitives::{BoundingBox, Change, U160, U256};
+use record_keeper::{Error, LogicError, DBState, Database};
+use record_keeper::rules::MutationRule;
+
+/// Child shards must start from a new origin block, and the areas of the child shards may never
+/// overlap. An expanded shard must keep all of its prior area.
+///
+/// A child genesis is an origin block like any other (`shard` and `prev` are both zero) so that it
+/// is detected as genesis the same way as the root; the link to the parent is the `SplitShard`
+/// change on the parent chain, which also stores the genesis header.
+pub struct ShardSplits;
+impl ShardSplits {
+    /// Get the id of the shard the state belongs to.
+    fn shard_of(state: &DBState) -> Result<U256, Error> {
+        let hash = state.get_current_block_hash();
+        let header = state.get_block_header(&hash)?;
+        Ok(if header.shard.is_zero() { hash } else { header.shard })
+    }
+
+    /// Make sure `bounds` does not overlap any of the other children.
+    fn check_overlap(children: &Vec<(U256, BoundingBox)>, id: &U256, bounds: BoundingBox) -> Result<(), Error> {
+        if children.iter().any(|&(ref cid, cbounds)| cid != id && cbounds.overlaps(bounds)) {
+            Err(LogicError::InvalidMutation("Child shards may not overlap".into()).into())
+        } else { Ok(()) }
+    }
+}
+
+impl MutationRule for ShardSplits {
+    fn is_valid(&self, state: &DBState, mutation: &Vec<(Change, U160)>, _cache: &mut Bin) -> Result<(), Error> {
+        let mut children: Option<Vec<(U256, BoundingBox)>> = None;
+
+        for &(ref change, _) in mutation { match change {
+            &Change::SplitShard { children: ref new_children } => {
+                if children.is_none() { children = Some(state.get_child_shards()?); }
+                let children = children.as_mut().unwrap();
+                let shard = Self::shard_of(state)?;
+
+                if new_children.is_empty() {
+                    return Err(LogicError::InvalidMutation("A split must create at least one shard".into()).into());
+                }
+
+                for &(bounds, ref genesis) in new_children.iter() {
+                    if !genesis.shard.is_zero() || !genesis.prev.is_zero() {
+                        return Err(LogicError::InvalidMutation("Child shard genesis must be an origin block".into()).into());
+                    }
+
+                    let id = genesis.calculate_hash();
+                    if id == shard || children.iter().any(|c| c.0 == id) {
+                        return Err(LogicError::Duplicate.into());
+                    }
+
+                    Self::check_overlap(children, &id, bounds)?;
+                    children.push((id, bounds));
+                }
+            },
+            &Change::ExpandShard { id, bounds } => {
+                if children.is_none() { children = Some(state.get_child_shards()?); }
+                let children = children.as_mut().unwrap();
+
+                let prior = children.iter().find(|c| c.0 == id).map(|c| c.1)
+                    .ok_or_else(|| LogicError::InvalidMutation("Cannot expand an unknown shard".into()))?;
+                if !bounds.contains_box(prior) {
+                    return Err(LogicError::InvalidMutation("An expanded shard must cover all of its prior area".into()).into());
+                }
+
+                Self::check_overlap(children, &id, bounds)?;
+                for child in children.iter_mut().filter(|c| c.0 == id) {
+                    child.1 = bounds;
+                }
+            },
+            _ => ()
+        }}
+
+        Ok(())
+    }
+
+    fn description(&self) -> &'static str {
+        "Child shards must follow this shard and may not overlap one another."
+    }
+}
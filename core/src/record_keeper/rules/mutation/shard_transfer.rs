@@ -0,0 +1,122 @@
+use bin::Bin;
+use primitives::{BlockHeader, Change, ShardTransferProof, U160, U256};
+use record_keeper::{Error, LogicError, DBState, Database};
+use record_keeper::rules::MutationRule;
+use record_keeper::key::NetworkEntry;
+use std::collections::HashSet;
+use std::fmt;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// A view of the header chains of other shards. Headers only become part of a chain once they pass
+/// the header verifier of the network. Claims are only accepted if the block they prove the
+/// transfer was made in is known to be final on its shard, and child block references must be for
+/// a known header.
+pub trait ShardHeaders: Send + Sync {
+    /// Whether the header is part of the chain of `shard` and at least `depth` blocks below its
+    /// head, so it can no longer be reorged.
+    fn is_final(&self, shard: &U256, header: &BlockHeader, depth: u64) -> bool;
+
+    /// The height of a block on the chain of `shard`, if its header is known.
+    fn get_height(&self, shard: &U256, block: &U256) -> Option<u64>;
+
+    /// The hash and height of the highest known header on the chain of `shard`.
+    fn get_head(&self, shard: &U256) -> Option<(U256, u64)>;
+}
+
+impl Debug for ShardHeaders {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.write_str("ShardHeaders")
+    }
+}
+
+
+/// Cross-shard transfers must go to a different shard and use an id which has not been used before.
+/// Claims must prove the matching transfer was made to this shard in a final block of the source
+/// shard, and may only be made once.
+pub struct ShardTransfers {
+    /// Source of the headers of other shards; without it no claims can be verified
+    pub headers: Option<Arc<ShardHeaders>>,
+
+    /// Number of blocks a transfer must be buried under on its shard before it can be claimed
+    pub finality_depth: u64
+}
+
+impl ShardTransfers {
+    pub fn new(headers: Option<Arc<ShardHeaders>>, finality_depth: u64) -> ShardTransfers {
+        ShardTransfers { headers, finality_depth }
+    }
+
+    /// Get the id of the shard the state belongs to.
+    fn shard_of(state: &DBState) -> Result<U256, Error> {
+        let hash = state.get_current_block_hash();
+        let header = state.get_block_header(&hash)?;
+        Ok(if header.shard.is_zero() { hash } else { header.shard })
+    }
+
+    /// Check the claim proof shows a transfer with the given id to `shard` was accepted on
+    /// `from_shard`.
+    fn check_claim(&self, shard: &U256, id: &U256, from_shard: &U256, proof: &Bin) -> Result<(), Error> {
+        let headers = self.headers.as_ref()
+            .ok_or_else(|| LogicError::InvalidMutation("Cannot verify claims without the headers of other shards".into()))?;
+
+        let proof = ShardTransferProof::decode(proof)
+            .map_err(|e| LogicError::InvalidMutation(format!("Could not deserialize claim proof: {}", e)))?;
+
+        if proof.header.shard != *from_shard {
+            return Err(LogicError::InvalidMutation("Claim proof is from a different shard".into()).into());
+        }
+
+        if !proof.is_included() {
+            return Err(LogicError::InvalidMerkleRoot.into());
+        }
+
+        match proof.find_transfer(id) {
+            Some(&Change::ShardTransfer { ref to_shard, .. }) if to_shard == shard => {},
+            _ => return Err(LogicError::InvalidMutation("Claim proof does not contain a transfer to this shard".into()).into())
+        }
+
+        if !headers.is_final(from_shard, &proof.header, self.finality_depth) {
+            return Err(LogicError::InvalidMutation("Claim proof is not from a final block of the source shard".into()).into());
+        }
+
+        Ok(())
+    }
+}
+
+impl MutationRule for ShardTransfers {
+    fn is_valid(&self, state: &DBState, mutation: &Vec<(Change, U160)>, _cache: &mut Bin) -> Result<(), Error> {
+        let mut shard = None;
+        let mut locks = HashSet::new();
+        let mut claims = HashSet::new();
+
+        for &(ref change, _) in mutation { match change {
+            &Change::ShardTransfer { id, to_shard, .. } => {
+                if shard.is_none() { shard = Some(Self::shard_of(state)?); }
+                if shard == Some(to_shard) {
+                    return Err(LogicError::InvalidMutation("Cannot make a cross-shard transfer to the same shard".into()).into());
+                }
+
+                if !locks.insert(id) || state._get(NetworkEntry::ShardLock(id).into()).is_ok() {
+                    return Err(LogicError::Duplicate.into());
+                }
+            },
+            &Change::ShardClaim { id, from_shard, ref proof } => {
+                if shard.is_none() { shard = Some(Self::shard_of(state)?); }
+
+                if !claims.insert((from_shard, id)) || state._get(NetworkEntry::ShardClaim(from_shard, id).into()).is_ok() {
+                    return Err(LogicError::Duplicate.into());
+                }
+
+                self.check_claim(shard.as_ref().unwrap(), &id, &from_shard, proof)?;
+            },
+            _ => ()
+        }}
+
+        Ok(())
+    }
+
+    fn description(&self) -> &'static str {
+        "Cross-shard transfers must be unique and claims must prove the transfer was made."
+    }
+}
@@ -1,11 +1,12 @@
 use bin::Bin;
 use primitives::{Change, U160};
-use record_keeper::{Error, LogicError, DBState, Database};
+use record_keeper::{Error, LogicError, DBState, Database, map_not_found};
 use record_keeper::rules::MutationRule;
 use std::collections::HashMap;
 use record_keeper::key::NetworkEntry;
 
-/// Make sure there are enough shares for transfers and slash txns. Also make sure
+/// Make sure there are enough shares for transfers and slash txns. Also make sure transfers are
+/// sent by the owner of the shares (or the admin).
 pub struct Shares;
 impl MutationRule for Shares {
     fn is_valid(&self, state: &DBState, mutation: &Vec<(Change, U160)>, _cache: &mut Bin) -> Result<(), Error> {
@@ -13,7 +14,13 @@ impl MutationRule for Shares {
         let mut senders: HashMap<U160, u64> = HashMap::new();
 
         for &(ref change, creator) in mutation {
-            if let &Change::Transfer{from, ref to} = change {
+            let transfer = match change {
+                &Change::Transfer{from, ref to} => Some((from, to)),
+                &Change::ShardTransfer{from, ref to, ..} => Some((from, to)),
+                _ => None
+            };
+
+            if let Some((from, to)) = transfer {
                 if from != creator && from != state.get_obj(NetworkEntry::AdminKeyID.into())? {
                     // must be created by the sender or by the admin.
                     return Err(LogicError::InvalidSigner.into())
@@ -36,7 +43,7 @@ impl MutationRule for Shares {
         }
 
         for (sender, amount) in senders {
-            let stake = state.get_validator_stake(sender)?;
+            let stake = map_not_found(state.get_validator_stake(sender), 0)?;
             if stake < amount {
                 // cannot send more shares than the sender has.
                 return Err(LogicError::NotEnoughShares.into())
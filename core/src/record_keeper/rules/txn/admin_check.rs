@@ -4,14 +4,16 @@ use record_keeper::rules::TxnRule;
 use primitives::Change;
 use record_keeper::key::*;
 
-/// The txn creator must be the Admin if it contains an admin change
+/// The txn creator must be the Admin if it contains an admin change or changes the shard layout
 pub struct AdminCheck;
 impl TxnRule for AdminCheck {
     fn is_valid(&self, state: &DBState, txn: &Txn) -> Result<(), Error> {
         let mut contains_admin = false;
         for change in txn.mutation.changes.iter() {
             match change {
-                &Change::Admin {..} => { contains_admin = true; break; },
+                &Change::Admin {..} |
+                &Change::SplitShard {..} |
+                &Change::ExpandShard {..} => { contains_admin = true; break; },
                 _ => ()
             }
         }
@@ -26,6 +28,6 @@ impl TxnRule for AdminCheck {
     }
 
     fn description(&self) -> &'static str {
-        "Txns containing an Admin change must be signed by the admin key."
+        "Txns containing an Admin, SplitShard, or ExpandShard change must be signed by the admin key."
     }
 }
\ No newline at end of file
@@ -0,0 +1,156 @@
+use bincode::{self, serialize, Bounded, Infinite};
+use std::collections::{BTreeMap, HashSet};
+use openssl::pkey::PKey;
+use bin::{Bin, AsBin};
+use hash::hash_pub_key;
+use primitives::{Block, Txn, U160, U256};
+use signer::{sign_obj, verify_obj};
+use super::database::{Database, HeadRef, WriteBatch};
+use super::{Error, LogicError, DBState, RecordKeeperIndexingStrategy, key::*};
+
+/// A signed copy of the network state at a checkpoint block. A new node may import this instead of
+/// replaying the chain from genesis and then sync only the blocks which come after the checkpoint.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Height of the checkpoint block
+    pub height: u64,
+    /// The checkpoint block along with some of the blocks before it (lowest height first) so that
+    /// anything which needs to look back a little way (e.g. forging) can still do so.
+    pub blocks: Vec<Block>,
+    /// The txns of the included blocks, so they can be looked up and undone like any other
+    pub txns: Vec<Txn>,
+    /// Raw key/value pairs of the network-state keyspace at the checkpoint
+    pub entries: Vec<(Bin, Bin)>,
+    /// The ID of whoever signed the snapshot
+    pub creator: U160,
+    pub signature: Bin
+}
+
+impl Snapshot {
+    /// Take a snapshot of the database as it was at the block of the current chain at `height`,
+    /// including `history` blocks before it. The state is found by undoing the blocks above it, so
+    /// their undo information must not have been pruned.
+    pub fn create(db: &Database, height: u64, history: u64, key: &PKey) -> Result<Snapshot, Error> {
+        let checkpoint = db.get_current_block_of_height(height)?;
+        let mut state = DBState::new(db);
+        state.walk(&checkpoint)?;
+
+        // the network state at the checkpoint is the database with the undone changes on top
+        let prefix = Key::Network(NetworkEntry::Generic(Bin::new())).as_bin();
+        let mut entries: BTreeMap<Bin, Bin> = db._get_network_entries()?.into_iter().collect();
+        for (k, v) in state.compile()? {
+            if !k.starts_with(&prefix) { continue; }
+            match v {
+                Some(v) => { entries.insert(k, v); },
+                None => { entries.remove(&k); }
+            }
+        }
+        let entries: Vec<(Bin, Bin)> = entries.into_iter().collect();
+
+        let mut blocks = db.iter_down(checkpoint)
+            .take(history.saturating_add(1).min(height) as usize)
+            .map(|r| r.and_then(|(_, header)| db.complete_block(header)))
+            .collect::<Result<Vec<Block>, Error>>()?;
+        blocks.reverse();
+
+        let txns = blocks.iter()
+            .flat_map(|b| b.txns.iter())
+            .map(|hash| db.get_txn(*hash))
+            .collect::<Result<Vec<Txn>, Error>>()?;
+
+        let creator = hash_pub_key(&key.public_key_to_der().unwrap());
+        let signature = sign_obj(&(height, &blocks, &txns, &entries), key);
+
+        Ok(Snapshot { height, blocks, txns, entries, creator, signature })
+    }
+
+    /// Verify the snapshot was signed by the given key and is internally consistent.
+    pub fn verify(&self, key: &PKey) -> Result<(), Error> {
+        let der = key.public_key_to_der().map_err(|_| LogicError::InvalidSignature)?;
+        if hash_pub_key(&der) != self.creator ||
+           !verify_obj(&(self.height, &self.blocks, &self.txns, &self.entries), &self.signature, key) {
+            return Err(LogicError::InvalidSignature.into());
+        }
+
+        // genesis is height 1, so there cannot be more blocks than the height
+        if self.blocks.is_empty() || (self.blocks.len() as u64) > self.height {
+            return Err(LogicError::InvalidSnapshot("Snapshot has an invalid block history".into()).into());
+        }
+
+        for pair in self.blocks.windows(2) {
+            if pair[1].prev != pair[0].calculate_hash() {
+                return Err(LogicError::MissingPrevious.into());
+            }
+        }
+
+        let txns: HashSet<U256> = self.txns.iter().map(Txn::calculate_hash).collect();
+        for block in self.blocks.iter() {
+            if block.merkle_root != Block::calculate_merkle_root(&block.txns) {
+                return Err(LogicError::InvalidMerkleRoot.into());
+            }
+            if block.txns.iter().any(|hash| !txns.contains(hash)) {
+                return Err(LogicError::InvalidSnapshot("Snapshot is missing txns of its blocks".into()).into());
+            }
+        }
+
+        let prefix = Key::Network(NetworkEntry::Generic(Bin::new())).as_bin();
+        if self.entries.iter().any(|&(ref k, _)| !k.starts_with(&prefix)) {
+            return Err(LogicError::InvalidSnapshot("Snapshot contains entries outside of the network state".into()).into());
+        }
+
+        Ok(())
+    }
+
+    /// Construct a write batch which replaces the network state of `db` with that of the snapshot
+    /// and moves the head to the checkpoint block. The snapshot should be verified first.
+    pub fn compile(&self, db: &Database) -> Result<WriteBatch, Error> {
+        let first_height = self.height + 1 - self.blocks.len() as u64;
+        if first_height == 1 && db.get_current_block_of_height(1)? != self.blocks[0].calculate_hash() {
+            return Err(LogicError::InvalidSnapshot("Snapshot does not share our genesis block".into()).into());
+        }
+
+        let mut wb = WriteBatch::default();
+
+        // whatever state we had (i.e. from genesis) is superseded
+        for (key, _) in db._get_network_entries()? {
+            wb.delete(&key);
+        }
+        for &(ref key, ref value) in self.entries.iter() {
+            wb.put(key, value);
+        }
+
+        for (i, block) in self.blocks.iter().enumerate() {
+            let height = first_height + i as u64;
+            let hash = block.calculate_hash();
+
+            wb.put(&Key::from(BlockchainEntry::BlockHeader(hash)).as_bin(), &serialize(&block.header, Infinite).unwrap());
+            wb.put(&Key::from(BlockchainEntry::TxnList(block.merkle_root)).as_bin(), &serialize(&block.txns, Infinite).unwrap());
+            wb.put(&Key::from(CacheEntry::BlocksByHeight(height)).as_bin(), &serialize(&vec![hash], Infinite).unwrap());
+            wb.put(&Key::from(CacheEntry::HeightByBlock(hash)).as_bin(), &serialize(&height, Bounded(8)).unwrap());
+
+            if db.get_index_strategy().includes(RecordKeeperIndexingStrategy::Standard) {
+                let blocks: HashSet<U256> = vec![hash].into_iter().collect();
+                for txn in block.txns.iter() {
+                    wb.put(&Key::from(CacheEntry::BlocksByTxn(*txn)).as_bin(), &serialize(&blocks, Infinite).unwrap());
+                }
+            }
+        }
+
+        for txn in self.txns.iter() {
+            wb.put(&Key::from(BlockchainEntry::Txn(txn.calculate_hash())).as_bin(), &serialize(txn, Infinite).unwrap());
+        }
+
+        let head = HeadRef { block: self.blocks.last().unwrap().calculate_hash(), height: self.height };
+        wb.put(&Key::from(CacheEntry::CurrentHead).as_bin(), &serialize(&head, Bounded(40)).unwrap());
+
+        Ok(wb)
+    }
+
+    pub fn decode(raw: &[u8]) -> Result<Snapshot, Error> {
+        Ok(bincode::deserialize(raw)?)
+    }
+
+    pub fn encode(&self) -> Bin {
+        serialize(self, Infinite).unwrap()
+    }
+}
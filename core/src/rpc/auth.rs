@@ -0,0 +1,167 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Read, Write};
+#[cfg(not(target_os = "windows"))]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+
+use crypto::util::fixed_time_eq;
+use hyper::header::{Authorization, Basic, Bearer, Headers};
+use parking_lot::RwLock;
+use rand::{self, Rng};
+
+use base16;
+use rpc::types::RPCHandler;
+
+/// The user name which goes with the password stored in a cookie file.
+pub const COOKIE_USER: &str = "__cookie__";
+
+/// What a client is allowed to do over RPC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    /// Only methods which look at the state of the node may be called
+    ReadOnly,
+    /// Methods which change the node or sign with its keys may also be called
+    Privileged
+}
+
+/// Secrets a client presents in the `Authorization` header to gain privileged access.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Credentials {
+    Basic { user: String, password: String },
+    Bearer(String)
+}
+
+impl Credentials {
+    /// Generate a random password and write it to a cookie file, so clients which can read the
+    /// storage directory are able to authenticate without any configuration.
+    pub fn create_cookie(path: &Path) -> io::Result<Credentials> {
+        let secret: [u8; 32] = rand::thread_rng().gen();
+        let password = base16::from_bin(&secret);
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+
+        // only the user running the node should be able to read it; the mode only applies when the
+        // file is created, so an existing cookie has its permissions set before it is written
+        #[cfg(not(target_os = "windows"))]
+        {
+            options.mode(0o600);
+            if path.exists() {
+                fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+            }
+        }
+
+        options.open(path)?.write_all(password.as_bytes())?;
+
+        Ok(Credentials::Basic { user: COOKIE_USER.into(), password })
+    }
+
+    /// Read the credentials from a cookie file written by `create_cookie`.
+    pub fn read_cookie(path: &Path) -> io::Result<Credentials> {
+        let mut password = String::new();
+        fs::File::open(path)?.read_to_string(&mut password)?;
+
+        Ok(Credentials::Basic { user: COOKIE_USER.into(), password: password.trim().into() })
+    }
+
+    /// Set the `Authorization` header of a request to present these credentials.
+    pub fn apply(&self, headers: &mut Headers) {
+        match *self {
+            Credentials::Basic { ref user, ref password } => headers.set(Authorization(Basic {
+                username: user.clone(),
+                password: Some(password.clone())
+            })),
+            Credentials::Bearer(ref token) => headers.set(Authorization(Bearer { token: token.clone() }))
+        }
+    }
+
+    /// Check if the `Authorization` header of a request presents these credentials.
+    fn matches(&self, headers: &Headers) -> bool {
+        match *self {
+            Credentials::Basic { ref user, ref password } => headers.get::<Authorization<Basic>>().map_or(false, |auth| {
+                let given = auth.password.as_ref().map_or("", |p| p.as_str());
+                // both are compared so the time taken does not tell which was wrong
+                fixed_time_eq(auth.username.as_bytes(), user.as_bytes()) &
+                    fixed_time_eq(given.as_bytes(), password.as_bytes())
+            }),
+            Credentials::Bearer(ref token) => headers.get::<Authorization<Bearer>>()
+                .map_or(false, |auth| fixed_time_eq(auth.token.as_bytes(), token.as_bytes()))
+        }
+    }
+}
+
+/// Decides what each RPC client may call. Without any credentials, every client has privileged
+/// access; otherwise only clients presenting one of the credentials do. Clients with read-only
+/// access may only call the methods registered as read-only.
+pub struct AccessControl {
+    credentials: Vec<Credentials>,
+
+    /// Names of the methods which are open to read-only clients
+    read_only: RwLock<HashSet<String>>
+}
+
+impl AccessControl {
+    pub fn new(credentials: Vec<Credentials>) -> AccessControl {
+        AccessControl {
+            credentials,
+            read_only: RwLock::new(HashSet::new())
+        }
+    }
+
+    /// True if clients have to authenticate for privileged access.
+    pub fn is_enabled(&self) -> bool {
+        !self.credentials.is_empty()
+    }
+
+    /// Remember which of the methods a handler adds are open to read-only clients.
+    pub fn register<H: RPCHandler>(&self) {
+        self.read_only.write().extend(H::read_only_methods().iter().map(|m| m.to_string()));
+    }
+
+    /// Open a single method to read-only clients.
+    pub fn allow_read_only(&self, method: &str) {
+        self.read_only.write().insert(method.into());
+    }
+
+    /// Find the access of a client from the headers of its request. Clients without headers, such
+    /// as WebSocket sessions, cannot authenticate.
+    pub fn check(&self, headers: Option<&Headers>) -> Access {
+        if !self.is_enabled() {
+            return Access::Privileged;
+        }
+
+        match headers {
+            Some(h) if self.credentials.iter().any(|c| c.matches(h)) => Access::Privileged,
+            _ => Access::ReadOnly
+        }
+    }
+
+    /// Check if a client with the given access may call a method. Methods which were never
+    /// registered as read-only need privileged access.
+    pub fn allows(&self, method: &str, access: Access) -> bool {
+        access == Access::Privileged || self.read_only.read().contains(method)
+    }
+}
+
+
+#[test]
+fn privileged_methods_need_credentials() {
+    let access = AccessControl::new(vec![Credentials::Bearer("secret".into())]);
+    access.allow_read_only("get_block");
+
+    let mut headers = Headers::new();
+    assert_eq!(access.check(Some(&headers)), Access::ReadOnly);
+    Credentials::Bearer("wrong".into()).apply(&mut headers);
+    assert_eq!(access.check(Some(&headers)), Access::ReadOnly);
+    Credentials::Bearer("secret".into()).apply(&mut headers);
+    assert_eq!(access.check(Some(&headers)), Access::Privileged);
+    assert_eq!(access.check(None), Access::ReadOnly);
+
+    assert!(access.allows("get_block", Access::ReadOnly));
+    assert!(!access.allows("stop", Access::ReadOnly));
+    assert!(access.allows("stop", Access::Privileged));
+    assert!(!access.allows("not_registered", Access::ReadOnly));
+
+    assert_eq!(AccessControl::new(Vec::new()).check(None), Access::Privileged);
+}
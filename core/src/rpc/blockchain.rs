@@ -7,14 +7,20 @@ use std::result::Result;
 use std::sync::Arc;
 use openssl::pkey::PKey;
 use std::collections::HashSet;
+use std::cmp::min;
+use serde::de::DeserializeOwned;
+use serde_json::from_value;
 
 use bin::*;
 use primitives::*;
 use time::Time;
-use record_keeper::RecordKeeper;
+use record_keeper::{RecordKeeper, Snapshot};
 use record_keeper::Error as RKErr;
 use hash::hash_pub_key;
 
+/// The most items which will be returned at once by a paginated method.
+const MAX_PAGE_SIZE: usize = 100;
+
 pub struct BlockchainRPC {
     rk: Arc<RecordKeeper>,
     forge_key: PKey
@@ -114,6 +120,14 @@ impl BlockTxnRPC {
     }
 }
 
+/// Part of a list which is too long to return at once. Pass `next` as the cursor to get the
+/// following page; it is null on the last page.
+#[derive(Serialize)]
+struct PageRPC<T, C> {
+    items: Vec<T>,
+    next: Option<C>
+}
+
 #[derive(Serialize)]
 struct TxnRPC {
     hash: JU256,
@@ -122,6 +136,7 @@ struct TxnRPC {
     mutation: JMutation,
     signature: JBin,
     size: u64,
+    /// Blocks which include the txn; null if it is pending or the node does not index them
     block: Option<HashSet<JU256>>
 }
 
@@ -132,14 +147,49 @@ impl TxnRPC {
             timestamp: txn.timestamp,
             creator: txn.creator.into(),
             size: txn.calculate_size() as u64,
-            block: rk.get_txn_blocks(txn.calculate_hash())
-                .map(|o| o.map(|h| h.into_iter().map(Into::into).collect()))?,
+            block: match rk.get_txn_blocks(txn.calculate_hash()) {
+                Ok(o) => o.map(|h| h.into_iter().map(Into::into).collect()),
+                Err(RKErr::IndexDisabled(_)) => None,
+                Err(e) => return Err(e)
+            },
             mutation: txn.mutation.into(),
             signature: txn.signature.into()
         })
     }
 }
 
+#[derive(Serialize)]
+struct TxnProofRPC {
+    txn: JU256,
+    block: JU256,
+    merkle_root: JU256,
+    proof: Vec<(JU256, bool)>
+}
+
+impl TxnProofRPC {
+    /// Prove a txn is included in the block of the current chain which accepted it.
+    pub fn new(hash: U256, rk: &Arc<RecordKeeper>) -> Result<TxnProofRPC, Error> {
+        let blocks = rk.get_txn_blocks(hash).map_err(map_rk_err)?
+            .ok_or_else(|| Error::invalid_params("Txn is still pending."))?;
+
+        let mut main = None;
+        for b in blocks {
+            if rk.is_block_in_current_chain(&b).map_err(map_rk_err)? { main = Some(b); break; }
+        }
+        let main = main.ok_or_else(|| Error::invalid_params("Txn is not in the current chain."))?;
+
+        let block = rk.get_block(&main).map_err(map_rk_err)?;
+        let proof = block.merkle_proof(&hash).ok_or_else(Error::internal_error)?;
+
+        Ok(TxnProofRPC {
+            txn: hash.into(),
+            block: main.into(),
+            merkle_root: block.merkle_root.into(),
+            proof: proof.into_iter().map(|(h, left)| (h.into(), left)).collect()
+        })
+    }
+}
+
 impl RPCHandler for BlockchainRPC {
     fn add(this: &Arc<BlockchainRPC>, io: &mut MetaIoHandler<SocketMetadata, LogMiddleware>) {
 
@@ -155,18 +205,31 @@ impl RPCHandler for BlockchainRPC {
         d.add_method_with_meta("get_block_height", Self::get_block_height);
         d.add_method_with_meta("get_blocks_of_height", Self::get_blocks_of_height);
         d.add_method_with_meta("get_latest_blocks", Self::get_latest_blocks);
+        d.add_method_with_meta("get_blocks_range", Self::get_blocks_range);
         d.add_method_with_meta("get_plot_events", Self::get_plot_events);
         d.add_method_with_meta("get_block_header", Self::get_block_header);
         d.add_method_with_meta("get_block", Self::get_block);
         d.add_method_with_meta("get_txn", Self::get_txn);
         d.add_method_with_meta("get_txn_blocks", Self::get_txn_blocks);
+        d.add_method_with_meta("get_txn_proof", Self::get_txn_proof);
         d.add_method_with_meta("get_account_txns", Self::get_account_txns);
+        d.add_method_with_meta("get_txns_in_block", Self::get_txns_in_block);
         d.add_method_with_meta("get_txn_receive_time", Self::get_txn_receive_time);
+        d.add_method_with_meta("export_snapshot", Self::export_snapshot);
+        d.add_method_with_meta("import_snapshot", Self::import_snapshot);
 
         d.add_method_with_meta("sign_txn", Self::sign_txn);
 
         io.extend_with(d);
     }
+
+    fn read_only_methods() -> &'static [&'static str] {
+        &["get_chain_stats", "get_validator_key", "get_validator_stake", "get_current_block_hash",
+          "get_current_block_header", "get_current_block", "get_block_height", "get_blocks_of_height",
+          "get_latest_blocks", "get_blocks_range", "get_plot_events", "get_block_header", "get_block",
+          "get_txn", "get_txn_blocks", "get_txn_proof", "get_account_txns", "get_txns_in_block",
+          "get_txn_receive_time"]
+    }
 }
 
 impl BlockchainRPC {
@@ -244,6 +307,20 @@ impl BlockchainRPC {
         }
     }
 
+    /// Usage: get_blocks_range <from_height> <to_height>
+    fn get_blocks_range(&self, params: Params, _meta: SocketMetadata) -> RpcResult {
+        let (from, to): (u64, u64) = expect_two_args(params)?;
+        let from = from.max(1); // genesis is height 1
+        if to < from { return Err(Error::invalid_params("Range ends before it starts.")); }
+        if to - from >= MAX_PAGE_SIZE as u64 { return Err(Error::invalid_params("Range too large.")); }
+
+        to_rpc_res(self.rk.get_blocks_range(from, to).and_then(|blocks|
+            blocks.iter()
+                .map(|h| BlockHeaderRPC::new(h, &self.rk))
+                .collect::<Result<Vec<BlockHeaderRPC>, RKErr>>()
+        ))
+    }
+
     fn get_plot_events(&self, params: Params, _meta: SocketMetadata) -> RpcResult {
         let (plot_id, after_tick) = expect_two_args(params)?;
         to_rpc_res(self.rk.get_plot_events(plot_id, after_tick))
@@ -275,13 +352,47 @@ impl BlockchainRPC {
         )
     }
 
+    fn get_txn_proof(&self, params: Params, _meta: SocketMetadata) -> RpcResult {
+        let hash = expect_one_arg::<JU256>(params)?.into();
+        Ok(to_value(TxnProofRPC::new(hash, &self.rk)?).unwrap())
+    }
+
+    /// Usage: get_account_txns <account> [cursor] [limit]
+    ///
+    /// Txns of the current chain are listed newest first and the cursor is the `[height, index]`
+    /// of the last txn of the previous page, so pages stay consistent as new txns are added.
     fn get_account_txns(&self, params: Params, _meta: SocketMetadata) -> RpcResult {
-        let hash = expect_one_arg::<JU160>(params)?.into();
-        to_rpc_res(self.rk.get_account_txns(&hash).map(|k|
-            k.into_iter()
-           .map(JU256::from)
-           .collect::<HashSet<JU256>>()
-        ))
+        let (account, cursor, limit) = expect_page_args::<JU160, (u64, usize)>(params)?;
+        // ask for one more to find out if there is another page
+        let mut txns = self.rk.get_account_txns_before(&account.into(), cursor, limit + 1)
+            .map_err(map_rk_err)?;
+
+        let has_more = txns.len() > limit;
+        txns.truncate(limit);
+
+        to_rpc_res(Ok(PageRPC {
+            next: if has_more { txns.last().map(|&(height, index, _)| (height, index)) } else { None },
+            items: txns.into_iter().map(|(_, _, txn)| JU256::from(txn)).collect()
+        }))
+    }
+
+    /// Usage: get_txns_in_block <block_hash> [cursor] [limit]
+    ///
+    /// The cursor is the index of the first txn of the page within the block.
+    fn get_txns_in_block(&self, params: Params, _meta: SocketMetadata) -> RpcResult {
+        let (hash, cursor, limit) = expect_page_args::<JU256, usize>(params)?;
+        let block = self.rk.get_block(&hash.into()).map_err(map_rk_err)?;
+
+        let start = min(cursor.unwrap_or(0), block.txns.len());
+        let end = min(start + limit, block.txns.len());
+        let has_more = end < block.txns.len();
+
+        to_rpc_res(
+            block.txns[start..end].iter()
+                .map(|&h| BlockTxnRPC::new(h, &self.rk))
+                .collect::<Result<Vec<_>, RKErr>>()
+                .map(|items| PageRPC { items, next: if has_more { Some(end) } else { None } })
+        )
     }
 
     fn get_txn_receive_time(&self, params: Params, _meta: SocketMetadata) -> RpcResult {
@@ -289,6 +400,21 @@ impl BlockchainRPC {
         to_rpc_res(self.rk.get_txn_receive_time(hash))
     }
 
+    /// Usage: export_snapshot <height> <history>
+    fn export_snapshot(&self, params: Params, _meta: SocketMetadata) -> RpcResult {
+        let (height, history): (u64, u64) = expect_two_args(params)?;
+        into_rpc_res::<_, JBin>(self.rk.export_snapshot(height, history, &self.forge_key).map(|s| s.encode()))
+    }
+
+    fn import_snapshot(&self, params: Params, _meta: SocketMetadata) -> RpcResult {
+        let (snapshot, trusted_key) = expect_two_args::<JBin, JBin>(params)?;
+        let (snapshot, trusted_key): (Bin, Bin) = (snapshot.into(), trusted_key.into());
+        let snapshot = Snapshot::decode(&snapshot).map_err(map_rk_err)?;
+        let trusted_key = PKey::public_key_from_der(&trusted_key)
+            .map_err(|_| Error::invalid_params("Could not decode trusted key."))?;
+        into_rpc_res::<_, JU256>(self.rk.import_snapshot(snapshot, &trusted_key))
+    }
+
     fn sign_txn(&self, params: Params, _meta: SocketMetadata) -> RpcResult {
         let mut txn : Txn = expect_one_arg::<JTxn>(params)?.into();
         txn.creator = hash_pub_key(&self.forge_key.public_key_to_der().unwrap());
@@ -310,3 +436,28 @@ fn into_rpc_res<T, J>(r: Result<T, RKErr>) -> RpcResult
 {
     to_rpc_res::<J>( r.map(|v| v.into()) )
 }
+
+/// Read the `<key> [cursor] [limit]` arguments of a paginated method. A missing or null cursor
+/// starts from the first page.
+fn expect_page_args<K, C>(params: Params) -> Result<(K, Option<C>, usize), Error>
+    where K: DeserializeOwned,
+          C: DeserializeOwned
+{
+    let mut args = expect_array(params, 1..4)?.into_iter();
+    let key = from_value(args.next().unwrap())
+        .map_err(|e| Error::invalid_params(format!("{:?}", e)))?;
+    let cursor = match args.next() {
+        Some(v) => from_value(v).map_err(|e| Error::invalid_params(format!("{:?}", e)))?,
+        None => None
+    };
+    let limit = match args.next() {
+        Some(v) => from_value(v).map_err(|e| Error::invalid_params(format!("{:?}", e)))?,
+        None => MAX_PAGE_SIZE
+    };
+
+    if limit == 0 || limit > MAX_PAGE_SIZE {
+        return Err(Error::invalid_params(format!("Limit must be between 1 and {}.", MAX_PAGE_SIZE)));
+    }
+
+    Ok((key, cursor, limit))
+}
@@ -11,6 +11,7 @@ use serde_json::Value;
 use hyper::{Client, Method, Request, self};
 use hyper::header::{ContentLength, ContentType, Accept};
 use tokio_core::reactor::Core;
+use rpc::auth::Credentials;
 use tokio_core::reactor::Timeout;
 
 use futures::{Future, Stream};
@@ -36,7 +37,7 @@ impl JsonRpcRequest {
         }
     }
 
-    pub fn exec_sync(&self, host: SocketAddr) -> Result<JsonRpcResponse, hyper::Error> {
+    pub fn exec_sync(&self, host: SocketAddr, auth: Option<&Credentials>) -> Result<JsonRpcResponse, hyper::Error> {
 
         let data = serde_json::to_string(self).map_err(|e| {
 
@@ -56,6 +57,9 @@ impl JsonRpcRequest {
         req.headers_mut().set(ContentType::json());
         req.headers_mut().set(ContentLength(data.len() as u64));
         req.headers_mut().set(Accept::json());
+        if let Some(creds) = auth {
+            creds.apply(req.headers_mut());
+        }
 
         req.set_body(data);
 
@@ -21,6 +21,10 @@ impl RPCHandler for ControlRPC {
 
         io.extend_with(d);
     }
+
+    fn read_only_methods() -> &'static [&'static str] {
+        &[]
+    }
 }
 
 impl ControlRPC {
@@ -3,40 +3,74 @@ pub mod client;
 #[macro_use]
 mod types;
 
+mod auth;
 mod blockchain;
 mod control;
 mod network;
+mod subscriptions;
 
+use hyper;
 use jsonrpc_http_server::{ServerBuilder, Server};
+use jsonrpc_ws_server::{self, RequestContext};
 use std::net::SocketAddr;
+use std::sync::Arc;
 
+pub use rpc::auth::{Access, AccessControl, Credentials, COOKIE_USER};
 pub use rpc::blockchain::BlockchainRPC;
 pub use rpc::control::ControlRPC;
 pub use rpc::network::NetworkRPC;
+pub use rpc::subscriptions::SubscriptionRPC;
 
 pub use rpc::types::*;
 
 pub use jsonrpc_macros::IoDelegate;
 pub use jsonrpc_core::Error;
 pub use jsonrpc_core::MetaIoHandler;
+pub use jsonrpc_pubsub::{PubSubHandler, Session};
 
 pub struct RPC {
     server: Server,
+    ws_server: Option<jsonrpc_ws_server::Server>,
+    access: Arc<AccessControl>,
 }
 
 impl RPC {
 
-    pub fn build_handler() -> MetaIoHandler<SocketMetadata, LogMiddleware> {
-        MetaIoHandler::with_middleware(LogMiddleware)
+    pub fn build_handler(access: &Arc<AccessControl>) -> PubSubHandler<SocketMetadata, LogMiddleware> {
+        PubSubHandler::new(MetaIoHandler::with_middleware(LogMiddleware::new(Arc::clone(access))))
     }
 
-    pub fn run(bind_addr: SocketAddr, handlers: MetaIoHandler<SocketMetadata, LogMiddleware>) -> RPC {
+    /// Serve a set of handlers over HTTP. Clients get privileged access by presenting credentials
+    /// in the `Authorization` header, if access control has any.
+    pub fn run(bind_addr: SocketAddr, handlers: PubSubHandler<SocketMetadata, LogMiddleware>, access: Arc<AccessControl>) -> RPC {
+        let checker = Arc::clone(&access);
         RPC {
-            server: ServerBuilder::new(handlers).start_http(&bind_addr).expect("Could not start RPC Interface")
+            server: ServerBuilder::new(handlers)
+                .meta_extractor(move |req: &hyper::Request| SocketMetadata::default().with_access(checker.check(Some(req.headers()))))
+                .start_http(&bind_addr)
+                .expect("Could not start RPC Interface"),
+            ws_server: None,
+            access
         }
     }
 
+    /// Also serve a set of handlers over WebSocket. Subscriptions need a session to push
+    /// notifications to, so they only work over this interface. Sessions cannot authenticate, so
+    /// they only get privileged access if access control has no credentials.
+    pub fn with_ws(mut self, bind_addr: SocketAddr, handlers: PubSubHandler<SocketMetadata, LogMiddleware>) -> RPC {
+        let access = self.access.check(None);
+        self.ws_server = Some(
+            jsonrpc_ws_server::ServerBuilder::new(handlers)
+                .session_meta_extractor(move |context: &RequestContext|
+                    SocketMetadata::from(Arc::new(Session::new(context.sender()))).with_access(access))
+                .start(&bind_addr)
+                .expect("Could not start WebSocket RPC Interface")
+        );
+        self
+    }
+
     pub fn close(self) {
         self.server.close();
+        if let Some(ws) = self.ws_server { ws.close(); }
     }
 }
\ No newline at end of file
@@ -4,7 +4,8 @@ use jsonrpc_core::*;
 
 use network::client::*;
 use network::ShardMode;
-use primitives::{U256, U256_ZERO};
+use network::node::{Node, NodeEndpoint};
+use primitives::U256;
 
 use rpc::types::*;
 
@@ -13,6 +14,7 @@ use jsonrpc_macros::IoDelegate;
 use serde_json;
 
 use futures::prelude::*;
+use futures::future;
 use futures::sync::mpsc::UnboundedSender;
 use futures::sync::oneshot;
 
@@ -26,10 +28,18 @@ impl RPCHandler for NetworkRPC {
         d.add_method_with_meta("get_net_stats", NetworkRPC::get_net_stats);
         d.add_method_with_meta("get_peer_info", NetworkRPC::get_peer_info);
         d.add_method_with_meta("attach_network", NetworkRPC::attach_network);
+        d.add_method_with_meta("list_networks", NetworkRPC::list_networks);
         d.add_method_with_meta("add_node", NetworkRPC::add_node);
+        d.add_method_with_meta("remove_node", NetworkRPC::remove_node);
+        d.add_method_with_meta("ban_node", NetworkRPC::ban_node);
+        d.add_method_with_meta("list_known_nodes", NetworkRPC::list_known_nodes);
 
         io.extend_with(d);
     }
+
+    fn read_only_methods() -> &'static [&'static str] {
+        &["get_net_stats", "get_peer_info", "list_networks", "list_known_nodes"]
+    }
 }
 
 impl NetworkRPC {
@@ -66,52 +76,113 @@ impl NetworkRPC {
         //Ok(serde_json::to_value(self.net_client.get_peer_info()).unwrap())
     }
 
-    fn attach_network(&self, params: Params, _meta: SocketMetadata) -> RpcResult {
-        let args = parse_args_simple::<String>(params, 2..1000)?;
+    /// Usage: attach_network add <network_id> <primary|aux|queryonly>
+    ///        attach_network remove <network_id>
+    fn attach_network(&self, params: Params, _meta: SocketMetadata) -> RpcFuture {
+        let (args, network_id) = match parse_args_simple::<String>(params, 2..4)
+            .and_then(|args| parse_network_id(&args[1]).map(|id| (args, id))) {
+            Ok(r) => r,
+            Err(e) => return Box::new(future::err(e))
+        };
+
+        match (args[0].as_str(), args.get(2).map(String::as_str)) {
+            ("add", Some(mode)) => {
+                let mode = match mode {
+                    "primary" => ShardMode::Primary,
+                    "aux" => ShardMode::Auxillery,
+                    "queryonly" => ShardMode::QueryOnly,
+                    _ => return Box::new(future::err(Error::invalid_params(format!("Invalid network mode: {}", mode))))
+                };
+
+                let (tx, rx) = oneshot::channel();
+                tryf!(self.net_client.unbounded_send(ClientMsg::AttachNetwork(network_id, mode, tx)).map_err(|_| Error::internal_error()));
+
+                Box::new(rx
+                    .map_err(|_| Error::internal_error())
+                    .and_then(|r| r.map_err(|e| Error::invalid_params(e.to_string())))
+                    .map(Value::from))
+            },
+            ("add", None) => Box::new(future::err(Error::invalid_params("Missing network mode."))),
+            ("remove", _) => {
+                let (tx, rx) = oneshot::channel();
+                tryf!(self.net_client.unbounded_send(ClientMsg::DetachNetwork(network_id, tx)).map_err(|_| Error::internal_error()));
+
+                Box::new(rx
+                    .map_err(|_| Error::internal_error())
+                    .map(Value::Bool))
+            },
+            (op, _) => Box::new(future::err(Error::invalid_params(format!("Invalid operation: {}", op))))
+        }
+    }
 
-        let network_id: U256 = args[1].parse().unwrap_or(U256_ZERO);
+    fn list_networks(&self, _params: Params, _meta: SocketMetadata) -> RpcFuture {
+        let (tx, rx) = oneshot::channel();
+        tryf!(self.net_client.unbounded_send(ClientMsg::ListNetworks(tx)).map_err(|_| Error::internal_error()));
+
+        Box::new(rx
+            .map_err(|_| Error::internal_error())
+            .and_then(|n| serde_json::to_value(n)
+                .map_err(|_| Error::internal_error())))
+    }
 
-        let op = args[0].as_str();
+    /// Usage: add_node <host:port> <network_id>
+    fn add_node(&self, params: Params, _meta: SocketMetadata) -> RpcFuture {
+        self.node_request(params, ClientMsg::AddNode)
+    }
 
-        if op == "add" {
-            let mode = match args[2].as_str() {
-                "primary" => ShardMode::Primary,
-                "aux" => ShardMode::Auxillery,
-                "queryonly" => ShardMode::QueryOnly,
-                _ => {
-                    return Err(Error::invalid_params(format!("Invalid network mode")))
-                }
-            };
+    /// Usage: remove_node <host:port> <network_id>
+    fn remove_node(&self, params: Params, _meta: SocketMetadata) -> RpcFuture {
+        self.node_request(params, ClientMsg::DropNode)
+    }
 
-            //let (tx, rx) = oneshot::channel();
-            try!(self.net_client.unbounded_send(ClientMsg::AttachNetwork(network_id, mode)).map_err(|_| Error::internal_error()));
+    /// Usage: ban_node <host:port> <network_id>
+    /// Every node at the address the host resolves to is banned, whatever its port.
+    fn ban_node(&self, params: Params, _meta: SocketMetadata) -> RpcFuture {
+        self.node_request(params, ClientMsg::BanNode)
+    }
 
-            // TODO: Better handling
-            Ok(Value::Bool(true))
-        }
-        else if op == "remove" {
-            //let (tx, rx) = oneshot::channel();
-            try!(self.net_client.unbounded_send(ClientMsg::DetachNetwork(network_id)).map_err(|_| Error::internal_error()));
+    /// Usage: list_known_nodes <network_id>
+    fn list_known_nodes(&self, params: Params, _meta: SocketMetadata) -> RpcFuture {
+        let network_id = match expect_one_arg::<String>(params).and_then(|id| parse_network_id(&id)) {
+            Ok(id) => id,
+            Err(e) => return Box::new(future::err(e))
+        };
 
-            // TODO: Better handling
-            Ok(Value::Bool(true))
-        }
-        else {
-            Err(Error::invalid_params(format!("Invalid operation: {}", op)))
-        }
+        let (tx, rx) = oneshot::channel();
+        tryf!(self.net_client.unbounded_send(ClientMsg::GetKnownNodes(network_id, tx)).map_err(|_| Error::internal_error()));
+
+        Box::new(rx
+            .map_err(|_| Error::internal_error())
+            .and_then(|n| n.ok_or_else(|| Error::invalid_params("Network is not attached.")))
+            .and_then(|n| serde_json::to_value(n)
+                .map_err(|_| Error::internal_error())))
     }
 
-    fn add_node(&self, params: Params, _meta: SocketMetadata) -> RpcResult {
-        let args = parse_args_simple::<String>(params, 1..4)?;
+    /// Send a message about a node to the network client which replies with whether it succeeded.
+    fn node_request<F>(&self, params: Params, msg: F) -> RpcFuture
+        where F: FnOnce(U256, Node, oneshot::Sender<bool>) -> ClientMsg
+    {
+        let (node, network_id) = match parse_node_args(params) {
+            Ok(args) => args,
+            Err(e) => return Box::new(future::err(e))
+        };
 
-        if args.len() == 3 {
-            Ok(Value::String("Not implemented".into()))
-        }
-        else if args.len() == 1 && args[0] == "help" {
-            Ok(Value::String("Usage: add_node <host:port> <network_id> <add|remove>".into()))
-        }
-        else {
-            Err(Error::invalid_params("Incorrect number of parameters"))
-        }
+        let (tx, rx) = oneshot::channel();
+        tryf!(self.net_client.unbounded_send(msg(network_id, node, tx)).map_err(|_| Error::internal_error()));
+
+        Box::new(rx
+            .map_err(|_| Error::internal_error())
+            .map(Value::Bool))
     }
-}
\ No newline at end of file
+}
+
+fn parse_network_id(id: &str) -> Result<U256, Error> {
+    id.parse().map_err(|_| Error::invalid_params("Invalid network id."))
+}
+
+/// Read the `<host:port> <network_id>` arguments of the node methods.
+fn parse_node_args(params: Params) -> Result<(Node, U256), Error> {
+    let args = parse_args_simple::<String>(params, 2..3)?;
+    let endpoint = args[0].parse::<NodeEndpoint>().map_err(Error::invalid_params)?;
+    Ok((Node::new(endpoint), parse_network_id(&args[1])?))
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures::prelude::*;
+use futures::Sink as FuturesSink;
+use futures::future;
+use futures::sync::mpsc::channel;
+use jsonrpc_core::*;
+use jsonrpc_pubsub::{PubSubHandler, PubSubMetadata, Subscriber, SubscriptionId, Sink};
+use parking_lot::Mutex;
+use rand::{self, Rng};
+use serde::Serialize;
+use serde_json::{self, Map};
+
+use base16;
+use record_keeper::{RecordKeeper, RecordEvent, JRecordEvent, PlotEvent, JPlotEvent, PlotID};
+use rpc::auth::AccessControl;
+use rpc::types::*;
+use worker::WORKER;
+
+/// Number of events which may be waiting to be pushed out before record keeper has to wait.
+/// Subscribers never hold up this queue; one which cannot keep up is dropped instead.
+const EVENT_BUFFER: usize = 64;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Topic {
+    NewBlocks,
+    PendingTxns,
+    StateInvalidated,
+    PlotEvents(PlotID)
+}
+
+type Subscriptions = Arc<Mutex<HashMap<String, (Topic, Sink)>>>;
+
+/// Pushes record keeper events to clients as they happen instead of making them poll. This requires
+/// a session, so it is only available over WebSocket.
+pub struct SubscriptionRPC {
+    subscriptions: Subscriptions
+}
+
+impl SubscriptionRPC {
+    /// Create the subscription handler and start listening to record keeper.
+    pub fn new(rk: &RecordKeeper) -> Arc<SubscriptionRPC> {
+        let subscriptions: Subscriptions = Arc::new(Mutex::new(HashMap::new()));
+
+        let (tx, rx) = channel(EVENT_BUFFER);
+        rk.register_record_listener(tx);
+        let subs = Arc::clone(&subscriptions);
+        WORKER.spawn(rx.for_each(move |e| {
+            let topic = match e {
+                RecordEvent::NewBlock{..} => Some(Topic::NewBlocks),
+                RecordEvent::NewTxn{..} => Some(Topic::PendingTxns),
+                RecordEvent::StateInvalidated{..} => Some(Topic::StateInvalidated),
+                RecordEvent::ChildShards{..} => None
+            };
+            publish(&subs, |t| Some(*t) == topic, &JRecordEvent::from(e));
+            Ok(())
+        })).forget();
+
+        let (tx, rx) = channel(EVENT_BUFFER);
+        rk.register_game_listener(tx);
+        let subs = Arc::clone(&subscriptions);
+        WORKER.spawn(rx.for_each(move |e: PlotEvent| {
+            let (from, to) = (e.from, e.to.clone());
+            publish(&subs, |t| match *t {
+                Topic::PlotEvents(plot) => plot == from || to.contains(&plot),
+                _ => false
+            }, &JPlotEvent::from(e));
+            Ok(())
+        })).forget();
+
+        Arc::new(SubscriptionRPC { subscriptions })
+    }
+
+    /// Add the subscription methods, which are all open to read-only clients.
+    pub fn add(this: &Arc<SubscriptionRPC>, io: &mut PubSubHandler<SocketMetadata, LogMiddleware>, access: &AccessControl) {
+        Self::add_topic(this, io, access, "new_blocks", |_| Ok(Topic::NewBlocks));
+        Self::add_topic(this, io, access, "pending_txns", |_| Ok(Topic::PendingTxns));
+        Self::add_topic(this, io, access, "state_invalidated", |_| Ok(Topic::StateInvalidated));
+        Self::add_topic(this, io, access, "plot_events", |params| Ok(Topic::PlotEvents(expect_one_arg(params)?)));
+    }
+
+    /// Add the `subscribe_<name>` and `unsubscribe_<name>` methods for a topic. Notifications are
+    /// sent with `<name>` as the method.
+    fn add_topic<F>(this: &Arc<SubscriptionRPC>, io: &mut PubSubHandler<SocketMetadata, LogMiddleware>, access: &AccessControl, name: &'static str, parse: F)
+        where F: Fn(Params) -> Result<Topic, Error> + Send + Sync + 'static
+    {
+        access.allow_read_only(&format!("subscribe_{}", name));
+        access.allow_read_only(&format!("unsubscribe_{}", name));
+
+        let (sub, unsub) = (Arc::clone(this), Arc::clone(this));
+        io.add_subscription(
+            name,
+            (&*format!("subscribe_{}", name), move |params: Params, meta: SocketMetadata, subscriber: Subscriber| {
+                match parse(params) {
+                    Ok(topic) => sub.subscribe(topic, meta, subscriber),
+                    Err(e) => { subscriber.reject(e).ok(); }
+                }
+            }),
+            (&*format!("unsubscribe_{}", name), move |id: SubscriptionId| {
+                future::ok(Value::Bool(unsub.unsubscribe(id)))
+            })
+        );
+    }
+
+    /// Subscription IDs are random since unsubscribing is not tied to the session which subscribed,
+    /// so knowing the ID is what lets a client cancel a subscription.
+    fn subscribe(&self, topic: Topic, meta: SocketMetadata, subscriber: Subscriber) {
+        let secret: [u8; 16] = rand::thread_rng().gen();
+        let id = base16::from_bin(&secret);
+        if let Ok(sink) = subscriber.assign_id(SubscriptionId::String(id.clone())) {
+            debug!("New RPC subscription {}.", id);
+            self.subscriptions.lock().insert(id.clone(), (topic, sink));
+
+            // forget the subscription once the client goes away
+            if let Some(session) = meta.session() {
+                let subs = Arc::clone(&self.subscriptions);
+                session.on_drop(move || { subs.lock().remove(&id); });
+            }
+        }
+    }
+
+    fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        match id {
+            SubscriptionId::String(id) => self.subscriptions.lock().remove(&id).is_some(),
+            _ => false
+        }
+    }
+}
+
+/// Send an event to all subscribers of matching topics without waiting on any of them. Subscribers
+/// which can no longer be reached, or which have fallen too far behind to take another
+/// notification, are dropped.
+fn publish<F, T>(subscriptions: &Subscriptions, matches: F, event: &T)
+    where F: Fn(&Topic) -> bool, T: Serialize
+{
+    let result = serde_json::to_value(event).unwrap();
+    subscriptions.lock().retain(|id, &mut (ref topic, ref mut sink)| {
+        if !matches(topic) {
+            return true;
+        }
+
+        let mut params = Map::new();
+        params.insert("subscription".into(), Value::from(id.clone()));
+        params.insert("result".into(), result.clone());
+
+        match sink.start_send(Params::Map(params)) {
+            Ok(AsyncSink::Ready) => true,
+            Ok(AsyncSink::NotReady(_)) => {
+                debug!("Dropping RPC subscription {} since it is not keeping up.", id);
+                false
+            },
+            Err(_) => {
+                debug!("Dropping RPC subscription {}.", id);
+                false
+            }
+        }
+    });
+}
@@ -5,47 +5,94 @@ use std::sync::Arc;
 
 use jsonrpc_core;
 use jsonrpc_core::MetaIoHandler;
-use jsonrpc_core::futures::Future;
-use jsonrpc_core::error::Error;
+use jsonrpc_core::futures::{future, Future};
+use jsonrpc_core::error::{Error, ErrorCode};
+use jsonrpc_core::{Call, Output, Request, Response};
+use jsonrpc_pubsub::{PubSubMetadata, Session};
 pub use jsonrpc_core::Params;
 use serde_json::{Value, from_value};
 use serde::de::DeserializeOwned;
 
 use record_keeper::Error as RKErr;
+use rpc::auth::{Access, AccessControl};
 
 pub type RpcResult = Result<jsonrpc_core::Value, jsonrpc_core::Error>;
 pub type RpcFuture = Box<Future<Item=jsonrpc_core::Value, Error=jsonrpc_core::Error> + Send>;
 
 pub trait RPCHandler {
     fn add(this: &Arc<Self>, io: &mut MetaIoHandler<SocketMetadata, LogMiddleware>);
+
+    /// Methods added by this handler which only look at the state of the node. These are open to
+    /// every client. Any other method needs privileged access, so a method missing from this list
+    /// is denied rather than exposed.
+    fn read_only_methods() -> &'static [&'static str];
 }
 
 #[derive(Clone)]
 pub struct SocketMetadata {
 	addr: SocketAddr,
+	session: Option<Arc<Session>>,
+	access: Access,
 }
 
 impl Default for SocketMetadata {
 	fn default() -> Self {
-		SocketMetadata { addr: "0.0.0.0:0".parse().unwrap() }
+		SocketMetadata { addr: "0.0.0.0:0".parse().unwrap(), session: None, access: Access::ReadOnly }
 	}
 }
 
-/*impl SocketMetadata {
-	pub fn addr(&self) -> &SocketAddr {
+impl SocketMetadata {
+	/*pub fn addr(&self) -> &SocketAddr {
 		&self.addr
+	}*/
+
+	pub fn with_access(self, access: Access) -> SocketMetadata {
+		SocketMetadata { access, ..self }
 	}
-}*/
+
+	pub fn access(&self) -> Access {
+		self.access
+	}
+}
 
 impl jsonrpc_core::Metadata for SocketMetadata { }
 
+impl PubSubMetadata for SocketMetadata {
+	fn session(&self) -> Option<Arc<Session>> {
+		self.session.clone()
+	}
+}
+
 impl From<SocketAddr> for SocketMetadata {
 	fn from(addr: SocketAddr) -> SocketMetadata {
-		SocketMetadata { addr: addr }
+		SocketMetadata { addr: addr, ..SocketMetadata::default() }
 	}
 }
 
-pub struct LogMiddleware;
+impl From<Arc<Session>> for SocketMetadata {
+	fn from(session: Arc<Session>) -> SocketMetadata {
+		SocketMetadata { session: Some(session), ..SocketMetadata::default() }
+	}
+}
+
+/// Logs each request and turns away calls to methods the client does not have access to.
+pub struct LogMiddleware {
+	access: Arc<AccessControl>
+}
+
+impl LogMiddleware {
+	pub fn new(access: Arc<AccessControl>) -> LogMiddleware {
+		LogMiddleware { access }
+	}
+
+	fn allows(&self, call: &Call, access: Access) -> bool {
+		match *call {
+			Call::MethodCall(ref c) => self.access.allows(&c.method, access),
+			Call::Notification(ref n) => self.access.allows(&n.method, access),
+			Call::Invalid(_) => true
+		}
+	}
+}
 
 impl jsonrpc_core::Middleware<SocketMetadata> for LogMiddleware {
     type Future = jsonrpc_core::FutureResponse;
@@ -57,6 +104,16 @@ impl jsonrpc_core::Middleware<SocketMetadata> for LogMiddleware {
         let start = Instant::now();
 		debug!("Processing RPC request: {:?}", request);
 
+		let allowed = match request {
+			Request::Single(ref call) => self.allows(call, meta.access),
+			Request::Batch(ref calls) => calls.iter().all(|call| self.allows(call, meta.access))
+		};
+
+		if !allowed {
+			debug!("Denied RPC request which needs privileged access.");
+			return Box::new(future::ok(deny(request)));
+		}
+
 		Box::new(next(request, meta).map(move |res| {
 			debug!("Processing took: {:?}", start.elapsed());
 			res
@@ -64,6 +121,26 @@ impl jsonrpc_core::Middleware<SocketMetadata> for LogMiddleware {
     }
 }
 
+/// Answer every method call of a request with an error saying it needs privileged access.
+fn deny(request: Request) -> Option<Response> {
+	let output = |call: Call| match call {
+		Call::MethodCall(c) => Some(Output::from(Err(Error {
+			code: ErrorCode::ServerError(-32001),
+			message: "Privileged access required.".into(),
+			data: None
+		}), c.id, c.jsonrpc)),
+		_ => None
+	};
+
+	match request {
+		Request::Single(call) => output(call).map(Response::Single),
+		Request::Batch(calls) => {
+			let outputs: Vec<Output> = calls.into_iter().filter_map(output).collect();
+			if outputs.is_empty() { None } else { Some(Response::Batch(outputs)) }
+		}
+	}
+}
+
 pub fn expect_array(p: Params, size: Range<usize>) -> Result<Vec<Value>, Error> {
 	match p {
 		Params::Array(a) => {
@@ -120,6 +197,7 @@ pub fn map_rk_err(e: RKErr) -> Error {
         RKErr::Deserialize(msg) => Error::invalid_params(msg),
         RKErr::Logic(err) => Error::invalid_params(format!("{:?}", err)),
 		RKErr::OutOfMemory(msg) => Error::invalid_params(msg),
+		RKErr::IndexDisabled(msg) => Error { code: jsonrpc_core::ErrorCode::InvalidRequest, message: msg, data: None },
         RKErr::NotFound(..) => Error::invalid_request()
     }
 }